@@ -0,0 +1,165 @@
+// Copyright (c) 2018, Maarten de Vries <maarten@de-vri.es>
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//   list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//   this list of conditions and the following disclaimer in the documentation
+//   and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::path::Path;
+
+/// Compression format of a file.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Compression {
+	/// Gzip compression, as used by `.gz` files.
+	Gzip,
+}
+
+impl Compression {
+	/// The maximum size in bytes of the decompressed contents of a file read by a [`FileTracker`](crate::FileTracker).
+	///
+	/// Larger files are rejected, so that a small compressed file can not exhaust the available memory.
+	pub const MAX_DECOMPRESSED_SIZE: u64 = 1 << 30;
+
+	/// Detect the compression of a file from its magic bytes or extension.
+	///
+	/// Returns `None` if the file does not appear to be compressed.
+	pub fn detect(path: &Path, data: &[u8]) -> Option<Self> {
+		if data.starts_with(&[0x1F, 0x8B]) || path.extension().is_some_and(|extension| extension == "gz") {
+			Some(Self::Gzip)
+		} else {
+			None
+		}
+	}
+
+	/// Detect the compression of a file from its extension only.
+	///
+	/// Returns `None` if the file does not have the extension of a known compression format.
+	pub fn from_extension(path: &Path) -> Option<Self> {
+		if path.extension().is_some_and(|extension| extension == "gz") {
+			Some(Self::Gzip)
+		} else {
+			None
+		}
+	}
+}
+
+/// Decompress the contents of a file if it is compressed.
+///
+/// The compression is detected with [`Compression::detect`] if `detect_contents` is true,
+/// and with [`Compression::from_extension`] otherwise.
+///
+/// Returns the decompressed data and the compression it was decompressed from.
+/// Fails with [`std::io::ErrorKind::InvalidData`] if the decompressed data is larger than `max_size`.
+/// Without the `gzip` feature, the data is always returned as it is.
+#[cfg(feature = "gzip")]
+pub(crate) fn decompress(
+	path: &Path,
+	data: Vec<u8>,
+	detect_contents: bool,
+	max_size: u64,
+) -> std::io::Result<(Vec<u8>, Option<Compression>)> {
+	use std::io::Read;
+
+	let compression = if detect_contents {
+		Compression::detect(path, &data)
+	} else {
+		Compression::from_extension(path)
+	};
+	match compression {
+		None => Ok((data, None)),
+		Some(Compression::Gzip) => {
+			let mut decompressed = Vec::new();
+			flate2::read::MultiGzDecoder::new(data.as_slice())
+				.take(max_size.saturating_add(1))
+				.read_to_end(&mut decompressed)?;
+			if decompressed.len() as u64 > max_size {
+				let message = format!(
+					"decompressed file is larger than {} bytes: {}",
+					max_size,
+					path.display()
+				);
+				return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message));
+			}
+			Ok((decompressed, Some(Compression::Gzip)))
+		}
+	}
+}
+
+/// Decompress the contents of a file if it is compressed.
+///
+/// Returns the decompressed data and the compression it was decompressed from.
+/// Without the `gzip` feature, the data is always returned as it is.
+#[cfg(not(feature = "gzip"))]
+pub(crate) fn decompress(
+	_path: &Path,
+	data: Vec<u8>,
+	_detect_contents: bool,
+	_max_size: u64,
+) -> std::io::Result<(Vec<u8>, Option<Compression>)> {
+	Ok((data, None))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use assert2::assert;
+
+	#[test]
+	fn test_detect() {
+		assert!(Compression::detect(Path::new("log.txt.gz"), b"") == Some(Compression::Gzip));
+		assert!(Compression::detect(Path::new("log.txt"), b"\x1F\x8B\x08") == Some(Compression::Gzip));
+		assert!(Compression::detect(Path::new("log.txt"), b"hello") == None);
+		assert!(Compression::detect(Path::new("gz"), b"") == None);
+		assert!(Compression::from_extension(Path::new("log.txt.gz")) == Some(Compression::Gzip));
+		assert!(Compression::from_extension(Path::new("data.bin")) == None);
+	}
+
+	#[test]
+	#[cfg(feature = "gzip")]
+	fn test_decompress() {
+		use std::io::Write;
+
+		let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+		encoder.write_all(b"hello\nworld").unwrap();
+		let compressed = encoder.finish().unwrap();
+
+		let (data, compression) = decompress(Path::new("log.txt"), compressed.clone(), true, 100).unwrap();
+		assert!(data == b"hello\nworld");
+		assert!(compression == Some(Compression::Gzip));
+
+		let (data, compression) = decompress(Path::new("log.txt"), b"hello".to_vec(), true, 100).unwrap();
+		assert!(data == b"hello");
+		assert!(compression == None);
+
+		assert!(decompress(Path::new("log.txt.gz"), b"hello".to_vec(), true, 100).is_err());
+
+		// Without content detection, only the extension is used.
+		let (data, compression) = decompress(Path::new("data.bin"), compressed.clone(), false, 100).unwrap();
+		assert!(data.as_slice() == compressed.as_slice());
+		assert!(compression == None);
+		let (data, _) = decompress(Path::new("data.bin.gz"), compressed.clone(), false, 100).unwrap();
+		assert!(data == b"hello\nworld");
+
+		// The decompressed size is limited.
+		assert!(decompress(Path::new("log.txt"), compressed.clone(), true, 11).is_ok());
+		let error = decompress(Path::new("log.txt"), compressed, true, 10).unwrap_err();
+		assert!(error.kind() == std::io::ErrorKind::InvalidData);
+	}
+}
@@ -0,0 +1,411 @@
+// Copyright (c) 2018, Maarten de Vries <maarten@de-vri.es>
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//   list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//   this list of conditions and the following disclaimer in the documentation
+//   and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::io::{Error, ErrorKind};
+use std::ops::Range;
+
+/// Encoding of a text file.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Encoding {
+	/// UTF-8, with or without byte order mark.
+	Utf8,
+
+	/// Little endian UTF-16, with or without byte order mark.
+	Utf16Le,
+
+	/// Big endian UTF-16, with or without byte order mark.
+	Utf16Be,
+
+	/// ISO 8859-1, where every byte is decoded as the Unicode code point with the same value.
+	Latin1,
+}
+
+impl Encoding {
+	/// Detect the encoding of data from the byte order mark.
+	///
+	/// Returns the encoding and the length of the byte order mark,
+	/// or `None` if the data does not start with a byte order mark.
+	pub fn from_bom(data: &[u8]) -> Option<(Self, usize)> {
+		match data {
+			[0xEF, 0xBB, 0xBF, ..] => Some((Self::Utf8, 3)),
+			[0xFF, 0xFE, ..] => Some((Self::Utf16Le, 2)),
+			[0xFE, 0xFF, ..] => Some((Self::Utf16Be, 2)),
+			_ => None,
+		}
+	}
+}
+
+/// Mapping from byte offsets in decoded data to byte offsets in the original file.
+///
+/// The mapping consists of segments in which every character has the same length in the decoded data
+/// and in the original file.
+#[derive(Clone, Debug, Default)]
+pub struct OffsetMap {
+	/// The segments of the mapping, sorted by offset.
+	///
+	/// Without segments, offsets in the decoded data are the same as in the original file.
+	segments: Vec<Segment>,
+
+	/// The ranges in the decoded data where invalid data was replaced with U+FFFD REPLACEMENT CHARACTER.
+	replacements: Vec<Range<usize>>,
+}
+
+/// Segment of an offset mapping.
+#[derive(Copy, Clone, Debug)]
+struct Segment {
+	/// The offset of the start of the segment in the decoded data.
+	data_start: usize,
+
+	/// The offset of the start of the segment in the original file.
+	file_start: usize,
+
+	/// The length of each character in the decoded data.
+	data_len: usize,
+
+	/// The length of each character in the original file.
+	file_len: usize,
+}
+
+impl OffsetMap {
+	/// Create a mapping where offsets in the decoded data are the same as in the original file.
+	pub fn identity() -> Self {
+		Self::default()
+	}
+
+	/// Map a byte offset in the decoded data to a byte offset in the original file.
+	pub fn file_offset(&self, offset: usize) -> usize {
+		let index = self.segments.partition_point(|segment| segment.data_start <= offset);
+		let segment = match index.checked_sub(1) {
+			None => return offset,
+			Some(i) => &self.segments[i],
+		};
+		let relative = offset - segment.data_start;
+		let chars = relative / segment.data_len;
+		let rest = relative % segment.data_len;
+		segment.file_start + chars * segment.file_len + rest.min(segment.file_len - 1)
+	}
+
+	/// Map a byte offset in the original file to a byte offset in the decoded data.
+	///
+	/// This is the reverse of [`Self::file_offset`].
+	/// Offsets inside a character of the original file map to the inside of the decoded character.
+	pub fn data_offset(&self, offset: usize) -> usize {
+		let index = self.segments.partition_point(|segment| segment.file_start <= offset);
+		let segment = match index.checked_sub(1) {
+			None => return offset,
+			Some(i) => &self.segments[i],
+		};
+		let relative = offset - segment.file_start;
+		let chars = relative / segment.file_len;
+		let rest = relative % segment.file_len;
+		segment.data_start + chars * segment.data_len + rest.min(segment.data_len - 1)
+	}
+
+	/// Get the ranges in the decoded data where invalid data was replaced with U+FFFD REPLACEMENT CHARACTER.
+	pub fn replacements(&self) -> &[Range<usize>] {
+		&self.replacements
+	}
+}
+
+/// Builder for an offset mapping.
+struct OffsetMapBuilder {
+	map: OffsetMap,
+	data_offset: usize,
+	file_offset: usize,
+}
+
+impl OffsetMapBuilder {
+	/// Start a mapping for data that starts at the given offset in the original file.
+	fn new(file_offset: usize) -> Self {
+		let mut map = OffsetMap::identity();
+		if file_offset != 0 {
+			map.segments.push(Segment {
+				data_start: 0,
+				file_start: file_offset,
+				data_len: 1,
+				file_len: 1,
+			});
+		}
+		Self {
+			map,
+			data_offset: 0,
+			file_offset,
+		}
+	}
+
+	/// Add a character to the mapping.
+	fn push(&mut self, data_len: usize, file_len: usize) {
+		let extends_last = match self.map.segments.last() {
+			Some(last) => last.data_len == data_len && last.file_len == file_len,
+			None => data_len == file_len,
+		};
+		if !extends_last {
+			self.map.segments.push(Segment {
+				data_start: self.data_offset,
+				file_start: self.file_offset,
+				data_len,
+				file_len,
+			});
+		}
+		self.data_offset += data_len;
+		self.file_offset += file_len;
+	}
+
+	/// Add a U+FFFD REPLACEMENT CHARACTER that replaces invalid data in the original file.
+	fn push_replacement(&mut self, file_len: usize) {
+		let data_len = char::REPLACEMENT_CHARACTER.len_utf8();
+		self.map
+			.replacements
+			.push(self.data_offset..self.data_offset + data_len);
+		self.push(data_len, file_len);
+	}
+
+	/// Add a run of characters that have the same length in the decoded data and the original file.
+	fn push_verbatim(&mut self, len: usize) {
+		if len > 0 {
+			self.push(1, 1);
+			self.data_offset += len - 1;
+			self.file_offset += len - 1;
+		}
+	}
+}
+
+/// Decode the contents of a text file.
+///
+/// If `encoding` is `None`, the encoding is detected from the byte order mark.
+/// Without byte order mark, the data must be UTF-8.
+/// A byte order mark is removed from the decoded data, unless it does not match the requested encoding.
+///
+/// If `lossy` is true, invalid data is replaced with U+FFFD REPLACEMENT CHARACTER,
+/// in the same way as [`String::from_utf8_lossy`].
+/// Otherwise, invalid data results in an error.
+///
+/// Returns the decoded data, the encoding of the file and the mapping from decoded offsets to file offsets.
+pub fn decode(
+	mut data: Vec<u8>,
+	encoding: Option<Encoding>,
+	lossy: bool,
+) -> std::io::Result<(String, Encoding, OffsetMap)> {
+	let bom = Encoding::from_bom(&data);
+	let (encoding, bom_len) = match (encoding, bom) {
+		(None, None) => (Encoding::Utf8, 0),
+		(None, Some((detected, len))) => (detected, len),
+		(Some(encoding), Some((detected, len))) if encoding == detected => (encoding, len),
+		(Some(encoding), _) => (encoding, 0),
+	};
+
+	let mut offsets = OffsetMapBuilder::new(bom_len);
+	let decoded = match encoding {
+		Encoding::Utf8 if lossy => decode_utf8_lossy(&data[bom_len..], &mut offsets),
+		Encoding::Utf8 => {
+			data.drain(..bom_len);
+			offsets.push_verbatim(data.len());
+			String::from_utf8(data).map_err(|e| Error::new(ErrorKind::InvalidData, e))?
+		}
+		Encoding::Utf16Le => decode_utf16(&data[bom_len..], u16::from_le_bytes, lossy, &mut offsets)?,
+		Encoding::Utf16Be => decode_utf16(&data[bom_len..], u16::from_be_bytes, lossy, &mut offsets)?,
+		Encoding::Latin1 => {
+			let mut decoded = String::with_capacity(data.len());
+			for &byte in &data {
+				let c = char::from(byte);
+				offsets.push(c.len_utf8(), 1);
+				decoded.push(c);
+			}
+			decoded
+		}
+	};
+
+	Ok((decoded, encoding, offsets.map))
+}
+
+/// Decode UTF-8 data, replacing invalid data with U+FFFD REPLACEMENT CHARACTER.
+fn decode_utf8_lossy(data: &[u8], offsets: &mut OffsetMapBuilder) -> String {
+	let mut decoded = String::with_capacity(data.len());
+	for chunk in data.utf8_chunks() {
+		offsets.push_verbatim(chunk.valid().len());
+		decoded.push_str(chunk.valid());
+		if !chunk.invalid().is_empty() {
+			offsets.push_replacement(chunk.invalid().len());
+			decoded.push(char::REPLACEMENT_CHARACTER);
+		}
+	}
+	decoded
+}
+
+/// Decode UTF-16 data.
+///
+/// If `lossy` is true, invalid data is replaced with U+FFFD REPLACEMENT CHARACTER.
+fn decode_utf16(
+	data: &[u8],
+	from_bytes: fn([u8; 2]) -> u16,
+	lossy: bool,
+	offsets: &mut OffsetMapBuilder,
+) -> std::io::Result<String> {
+	let units = data.chunks_exact(2);
+	let trailing = units.remainder().len();
+	if trailing != 0 && !lossy {
+		return Err(Error::new(
+			ErrorKind::InvalidData,
+			"UTF-16 data has an odd number of bytes",
+		));
+	}
+
+	let units = units.map(|x| from_bytes([x[0], x[1]]));
+	let mut decoded = String::with_capacity(data.len() / 2);
+	for c in char::decode_utf16(units) {
+		match c {
+			Ok(c) => {
+				offsets.push(c.len_utf8(), c.len_utf16() * 2);
+				decoded.push(c);
+			}
+			Err(_) if lossy => {
+				offsets.push_replacement(2);
+				decoded.push(char::REPLACEMENT_CHARACTER);
+			}
+			Err(e) => return Err(Error::new(ErrorKind::InvalidData, e)),
+		}
+	}
+
+	if trailing != 0 {
+		offsets.push_replacement(trailing);
+		decoded.push(char::REPLACEMENT_CHARACTER);
+	}
+	Ok(decoded)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use assert2::assert;
+
+	fn utf16le(data: &str) -> Vec<u8> {
+		data.encode_utf16().flat_map(|x| x.to_le_bytes().to_vec()).collect()
+	}
+
+	fn utf16be(data: &str) -> Vec<u8> {
+		data.encode_utf16().flat_map(|x| x.to_be_bytes().to_vec()).collect()
+	}
+
+	#[test]
+	fn test_decode_utf8() {
+		let (decoded, encoding, offsets) = decode(Vec::from("aé"), None, false).unwrap();
+		assert!(decoded == "aé");
+		assert!(encoding == Encoding::Utf8);
+		assert!(offsets.file_offset(0) == 0);
+		assert!(offsets.file_offset(3) == 3);
+
+		let (decoded, encoding, offsets) = decode(Vec::from("\u{FEFF}aé"), None, false).unwrap();
+		assert!(decoded == "aé");
+		assert!(encoding == Encoding::Utf8);
+		assert!(offsets.file_offset(0) == 3);
+		assert!(offsets.file_offset(1) == 4);
+		assert!(offsets.file_offset(3) == 6);
+
+		let (decoded, _, offsets) = decode(Vec::from("\u{FEFF}"), None, false).unwrap();
+		assert!(decoded == "");
+		assert!(offsets.file_offset(0) == 3);
+
+		assert!(decode(vec![b'a', 0xFF], None, false).unwrap_err().kind() == ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn test_decode_utf16() {
+		let mut data = vec![0xFF, 0xFE];
+		data.extend(utf16le("aé😀b"));
+		let (decoded, encoding, offsets) = decode(data, None, false).unwrap();
+		assert!(decoded == "aé😀b");
+		assert!(encoding == Encoding::Utf16Le);
+		assert!(offsets.file_offset(0) == 2);
+		assert!(offsets.file_offset(1) == 4);
+		assert!(offsets.file_offset(3) == 6);
+		assert!(offsets.file_offset(7) == 10);
+		assert!(offsets.file_offset(8) == 12);
+
+		let mut data = vec![0xFE, 0xFF];
+		data.extend(utf16be("aé"));
+		let (decoded, encoding, offsets) = decode(data, None, false).unwrap();
+		assert!(decoded == "aé");
+		assert!(encoding == Encoding::Utf16Be);
+		assert!(offsets.file_offset(1) == 4);
+
+		// Without byte order mark, UTF-16 must be requested explicitly.
+		let (decoded, encoding, offsets) = decode(utf16be("ab"), Some(Encoding::Utf16Be), false).unwrap();
+		assert!(decoded == "ab");
+		assert!(encoding == Encoding::Utf16Be);
+		assert!(offsets.file_offset(1) == 2);
+
+		assert!(decode(vec![0xFF, 0xFE, b'a'], None, false).unwrap_err().kind() == ErrorKind::InvalidData);
+		assert!(decode(vec![0xFF, 0xFE, 0x00, 0xD8], None, false).unwrap_err().kind() == ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn test_decode_latin1() {
+		let (decoded, encoding, offsets) = decode(vec![b'a', 0xE9, b'b', b'c'], Some(Encoding::Latin1), false).unwrap();
+		assert!(decoded == "aébc");
+		assert!(encoding == Encoding::Latin1);
+		assert!(offsets.file_offset(0) == 0);
+		assert!(offsets.file_offset(1) == 1);
+		assert!(offsets.file_offset(3) == 2);
+		assert!(offsets.file_offset(4) == 3);
+		assert!(offsets.file_offset(5) == 4);
+
+		// A byte order mark for a different encoding is decoded as data.
+		let (decoded, _, _) = decode(vec![0xFF, 0xFE], Some(Encoding::Latin1), false).unwrap();
+		assert!(decoded == "ÿþ");
+	}
+
+	#[test]
+	fn test_decode_lossy() {
+		let (decoded, encoding, offsets) = decode(Vec::from(&b"ab\xFFc\n\xE2\x82d"[..]), None, true).unwrap();
+		assert!(decoded == "ab\u{FFFD}c\n\u{FFFD}d");
+		assert!(encoding == Encoding::Utf8);
+		assert!(offsets.replacements() == [2..5, 7..10]);
+		assert!(offsets.file_offset(2) == 2);
+		assert!(offsets.file_offset(5) == 3);
+		assert!(offsets.file_offset(6) == 4);
+		assert!(offsets.file_offset(7) == 5);
+		assert!(offsets.file_offset(10) == 7);
+		assert!(offsets.data_offset(3) == 5);
+		assert!(offsets.data_offset(5) == 7);
+		assert!(offsets.data_offset(7) == 10);
+
+		let (decoded, _, offsets) = decode(Vec::from(&b"\xEF\xBB\xBFa\xFF"[..]), None, true).unwrap();
+		assert!(decoded == "a\u{FFFD}");
+		assert!(offsets.replacements() == [1..4]);
+		assert!(offsets.file_offset(1) == 4);
+
+		// An unpaired surrogate and a trailing byte.
+		let (decoded, encoding, offsets) =
+			decode(vec![0xFF, 0xFE, b'a', 0, 0x00, 0xD8, b'b', 0, b'c'], None, true).unwrap();
+		assert!(decoded == "a\u{FFFD}b\u{FFFD}");
+		assert!(encoding == Encoding::Utf16Le);
+		assert!(offsets.replacements() == [1..4, 5..8]);
+		assert!(offsets.file_offset(4) == 6);
+		assert!(offsets.file_offset(5) == 8);
+
+		// Valid data has no replacements.
+		let (_, _, offsets) = decode(Vec::from("aé"), None, true).unwrap();
+		assert!(offsets.replacements().is_empty());
+	}
+}
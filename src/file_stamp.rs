@@ -0,0 +1,83 @@
+// Copyright (c) 2018, Maarten de Vries <maarten@de-vri.es>
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//   list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//   this list of conditions and the following disclaimer in the documentation
+//   and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::io::Read;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::FileHandle;
+use crate::FileMetadata;
+use crate::FileSystem;
+
+/// The state of a file when it was read, used to detect changes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileStamp {
+	/// The size of the file in bytes.
+	size: u64,
+
+	/// The modification time of the file, if the platform supports it.
+	modified: Option<SystemTime>,
+
+	/// A hash of the contents of the file.
+	hash: u64,
+}
+
+impl FileStamp {
+	/// Create a stamp for a file with the given metadata and contents.
+	pub fn new(metadata: &FileMetadata, contents: &[u8]) -> Self {
+		Self {
+			size: metadata.len,
+			modified: metadata.modified,
+			hash: hash_contents(contents),
+		}
+	}
+
+	/// Check if the file at the given path changed since the stamp was created.
+	///
+	/// A file with a different size is stale.
+	/// If the size and the modification time are unchanged, the file is not stale and its contents are not read.
+	/// Otherwise, the contents are read and compared with the hash of the contents when the stamp was created.
+	/// If the file system does not report modification times, the contents are always compared.
+	pub fn is_stale(&self, file_system: &impl FileSystem, path: &Path) -> std::io::Result<bool> {
+		let mut file = file_system.open(path)?;
+		let metadata = file.metadata()?;
+		if metadata.len != self.size {
+			return Ok(true);
+		}
+		if metadata.modified.is_some() && metadata.modified == self.modified {
+			return Ok(false);
+		}
+		let mut contents = Vec::new();
+		file.read_to_end(&mut contents)?;
+		Ok(hash_contents(&contents) != self.hash)
+	}
+}
+
+/// Compute a hash of the contents of a file.
+fn hash_contents(contents: &[u8]) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	hasher.write(contents);
+	hasher.finish()
+}
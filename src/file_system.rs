@@ -0,0 +1,376 @@
+// Copyright (c) 2018, Maarten de Vries <maarten@de-vri.es>
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//   list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//   this list of conditions and the following disclaimer in the documentation
+//   and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// File system that a [`FileTracker`](crate::FileTracker) reads files from.
+///
+/// Implementations are provided for the real file system ([`StdFileSystem`]),
+/// for files held in memory ([`MemoryFileSystem`])
+/// and for overlaying one file system on top of another ([`OverlayFileSystem`]).
+pub trait FileSystem {
+	/// An opened file.
+	type File: FileHandle;
+
+	/// Open a file for reading.
+	fn open(&self, path: &Path) -> std::io::Result<Self::File>;
+
+	/// Read the whole contents of a file.
+	fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+		let mut data = Vec::new();
+		self.open(path)?.read_to_end(&mut data)?;
+		Ok(data)
+	}
+
+	/// Get the metadata of a file.
+	fn metadata(&self, path: &Path) -> std::io::Result<FileMetadata>;
+
+	/// Get the canonical form of a path.
+	///
+	/// This is only used to detect symbolic link loops when walking directories.
+	/// The default implementation fails with [`std::io::ErrorKind::Unsupported`],
+	/// in which case the tracker only removes `.` components from directory paths.
+	fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+		let _ = path;
+		Err(std::io::ErrorKind::Unsupported.into())
+	}
+
+	/// Get the paths of the entries in a directory, in any order.
+	///
+	/// The default implementation fails with [`std::io::ErrorKind::Unsupported`].
+	fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+		let _ = path;
+		Err(std::io::ErrorKind::Unsupported.into())
+	}
+}
+
+/// A file opened by a [`FileSystem`].
+///
+/// The tracker takes the metadata of a file from the opened handle,
+/// so that the metadata belongs to the same file as the contents that are read from it.
+pub trait FileHandle: Read {
+	/// Get the metadata of the opened file.
+	fn metadata(&self) -> std::io::Result<FileMetadata>;
+}
+
+impl FileHandle for std::fs::File {
+	fn metadata(&self) -> std::io::Result<FileMetadata> {
+		Ok(std::fs::File::metadata(self)?.into())
+	}
+}
+
+/// Metadata of a file in a [`FileSystem`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileMetadata {
+	/// The size of the file in bytes.
+	pub len: u64,
+
+	/// The modification time of the file, if the file system supports it.
+	pub modified: Option<SystemTime>,
+
+	/// Whether the path refers to a directory.
+	pub is_dir: bool,
+}
+
+impl From<std::fs::Metadata> for FileMetadata {
+	fn from(other: std::fs::Metadata) -> Self {
+		Self {
+			len: other.len(),
+			modified: other.modified().ok(),
+			is_dir: other.is_dir(),
+		}
+	}
+}
+
+/// The real file system, accessed through [`std::fs`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct StdFileSystem;
+
+impl FileSystem for StdFileSystem {
+	type File = std::fs::File;
+
+	fn open(&self, path: &Path) -> std::io::Result<Self::File> {
+		std::fs::File::open(path)
+	}
+
+	fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+		std::fs::read(path)
+	}
+
+	fn metadata(&self, path: &Path) -> std::io::Result<FileMetadata> {
+		Ok(std::fs::metadata(path)?.into())
+	}
+
+	fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+		std::fs::canonicalize(path)
+	}
+
+	fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+		std::fs::read_dir(path)?.map(|entry| Ok(entry?.path())).collect()
+	}
+}
+
+/// File system that holds files in memory.
+///
+/// Paths are compared after removing `.` components, so `./a.cfg` and `a.cfg` refer to the same file.
+/// Files do not have a modification time, so changes are detected by size and contents.
+/// Directories exist implicitly for the parent directories of all files.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryFileSystem {
+	files: BTreeMap<PathBuf, Arc<[u8]>>,
+}
+
+impl MemoryFileSystem {
+	/// Create an empty in-memory file system.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add a file, or replace the contents of an existing file.
+	pub fn insert(&mut self, path: impl AsRef<Path>, contents: impl Into<Arc<[u8]>>) {
+		self.files.insert(remove_cur_dir(path.as_ref()), contents.into());
+	}
+
+	/// Remove a file.
+	///
+	/// Returns the contents of the removed file, or `None` if the file did not exist.
+	pub fn remove(&mut self, path: impl AsRef<Path>) -> Option<Arc<[u8]>> {
+		self.files.remove(&remove_cur_dir(path.as_ref()))
+	}
+
+	/// Get a file by path.
+	fn get(&self, path: &Path) -> std::io::Result<&Arc<[u8]>> {
+		self.files.get(&remove_cur_dir(path)).ok_or_else(|| {
+			let message = format!("file not found: {}", path.display());
+			std::io::Error::new(std::io::ErrorKind::NotFound, message)
+		})
+	}
+}
+
+impl FileHandle for std::io::Cursor<Arc<[u8]>> {
+	fn metadata(&self) -> std::io::Result<FileMetadata> {
+		Ok(FileMetadata {
+			len: self.get_ref().len() as u64,
+			modified: None,
+			is_dir: false,
+		})
+	}
+}
+
+impl FileSystem for MemoryFileSystem {
+	type File = std::io::Cursor<Arc<[u8]>>;
+
+	fn open(&self, path: &Path) -> std::io::Result<Self::File> {
+		Ok(std::io::Cursor::new(self.get(path)?.clone()))
+	}
+
+	fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+		Ok(self.get(path)?.to_vec())
+	}
+
+	fn metadata(&self, path: &Path) -> std::io::Result<FileMetadata> {
+		if !self.files.contains_key(&remove_cur_dir(path)) && !self.read_dir(path)?.is_empty() {
+			return Ok(FileMetadata {
+				len: 0,
+				modified: None,
+				is_dir: true,
+			});
+		}
+		Ok(FileMetadata {
+			len: self.get(path)?.len() as u64,
+			modified: None,
+			is_dir: false,
+		})
+	}
+
+	fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+		let dir = remove_cur_dir(path);
+		let names: BTreeSet<_> = self
+			.files
+			.keys()
+			.filter_map(|file| file.strip_prefix(&dir).ok()?.components().next())
+			.collect();
+		Ok(names.into_iter().map(|name| path.join(name)).collect())
+	}
+}
+
+/// File system that overlays one file system on top of another.
+///
+/// Files in the upper file system hide files with the same path in the lower file system.
+/// Files that are not found in the upper file system are read from the lower file system.
+/// This can be used to overlay unsaved editor buffers on top of the real file system.
+#[derive(Clone, Debug, Default)]
+pub struct OverlayFileSystem<Upper, Lower> {
+	/// The file system that is tried first.
+	pub upper: Upper,
+
+	/// The file system that is used for files not found in the upper file system.
+	pub lower: Lower,
+}
+
+impl<Upper, Lower> OverlayFileSystem<Upper, Lower> {
+	/// Create a new overlay file system.
+	pub fn new(upper: Upper, lower: Lower) -> Self {
+		Self { upper, lower }
+	}
+}
+
+/// An opened file in an [`OverlayFileSystem`].
+#[derive(Debug)]
+pub enum OverlayFile<Upper, Lower> {
+	/// A file from the upper file system.
+	Upper(Upper),
+
+	/// A file from the lower file system.
+	Lower(Lower),
+}
+
+impl<Upper: Read, Lower: Read> Read for OverlayFile<Upper, Lower> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		match self {
+			Self::Upper(file) => file.read(buf),
+			Self::Lower(file) => file.read(buf),
+		}
+	}
+}
+
+impl<Upper: FileHandle, Lower: FileHandle> FileHandle for OverlayFile<Upper, Lower> {
+	fn metadata(&self) -> std::io::Result<FileMetadata> {
+		match self {
+			Self::Upper(file) => file.metadata(),
+			Self::Lower(file) => file.metadata(),
+		}
+	}
+}
+
+/// Try an operation on the upper file system, and fall back to the lower file system if the file is not found.
+fn overlay<T>(
+	upper: impl FnOnce() -> std::io::Result<T>,
+	lower: impl FnOnce() -> std::io::Result<T>,
+) -> std::io::Result<T> {
+	match upper() {
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => lower(),
+		result => result,
+	}
+}
+
+impl<Upper: FileSystem, Lower: FileSystem> FileSystem for OverlayFileSystem<Upper, Lower> {
+	type File = OverlayFile<Upper::File, Lower::File>;
+
+	fn open(&self, path: &Path) -> std::io::Result<Self::File> {
+		overlay(
+			|| self.upper.open(path).map(OverlayFile::Upper),
+			|| self.lower.open(path).map(OverlayFile::Lower),
+		)
+	}
+
+	fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+		overlay(|| self.upper.read(path), || self.lower.read(path))
+	}
+
+	fn metadata(&self, path: &Path) -> std::io::Result<FileMetadata> {
+		overlay(|| self.upper.metadata(path), || self.lower.metadata(path))
+	}
+
+	/// Canonicalize a path with the lower file system.
+	///
+	/// Files in the upper file system should be added with the same path as they have in the lower file system,
+	/// so a path refers to the same file regardless of which file system it is read from.
+	fn canonicalize(&self, path: &Path) -> std::io::Result<PathBuf> {
+		self.lower.canonicalize(path)
+	}
+
+	/// List the entries of a directory in both file systems.
+	///
+	/// Fails if listing the directory fails in both file systems.
+	fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+		let entries = match (self.upper.read_dir(path), self.lower.read_dir(path)) {
+			(Err(e), Err(_)) => return Err(e),
+			(upper, lower) => upper.into_iter().chain(lower).flatten(),
+		};
+		let entries: BTreeSet<_> = entries.collect();
+		Ok(entries.into_iter().collect())
+	}
+}
+
+/// Remove `.` components from a path.
+pub(crate) fn remove_cur_dir(path: &Path) -> PathBuf {
+	path.components()
+		.filter(|component| component != &Component::CurDir)
+		.collect()
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use assert2::assert;
+
+	#[test]
+	fn test_memory_file_system() {
+		let mut fs = MemoryFileSystem::new();
+		fs.insert("a.cfg", &b"hello"[..]);
+		assert!(fs.read(Path::new("./a.cfg")).unwrap() == b"hello");
+		assert!(fs.metadata(Path::new("a.cfg")).unwrap().len == 5);
+
+		let mut file = fs.open(Path::new("a.cfg")).unwrap();
+		assert!(file.metadata().unwrap().len == 5);
+		let mut data = String::new();
+		file.read_to_string(&mut data).unwrap();
+		assert!(data == "hello");
+
+		fs.insert("dir/b.cfg", &b"b"[..]);
+		fs.insert("dir/sub/c.cfg", &b"c"[..]);
+		assert!(fs.metadata(Path::new("dir")).unwrap().is_dir);
+		assert!(!fs.metadata(Path::new("a.cfg")).unwrap().is_dir);
+		assert!(fs.read_dir(Path::new(".")).unwrap() == [Path::new("./a.cfg"), Path::new("./dir")]);
+		assert!(fs.read_dir(Path::new("dir")).unwrap() == [Path::new("dir/b.cfg"), Path::new("dir/sub")]);
+
+		assert!(fs.remove("a.cfg").is_some());
+		assert!(fs.read(Path::new("a.cfg")).unwrap_err().kind() == std::io::ErrorKind::NotFound);
+		assert!(fs.canonicalize(Path::new("a.cfg")).unwrap_err().kind() == std::io::ErrorKind::Unsupported);
+	}
+
+	#[test]
+	fn test_overlay_file_system() {
+		let mut upper = MemoryFileSystem::new();
+		let mut lower = MemoryFileSystem::new();
+		upper.insert("a.cfg", &b"upper"[..]);
+		lower.insert("a.cfg", &b"lower"[..]);
+		lower.insert("b.cfg", &b"lower b"[..]);
+		let fs = OverlayFileSystem::new(upper, lower);
+
+		assert!(fs.read(Path::new("a.cfg")).unwrap() == b"upper");
+		assert!(fs.read(Path::new("b.cfg")).unwrap() == b"lower b");
+		assert!(fs.metadata(Path::new("b.cfg")).unwrap().len == 7);
+		assert!(fs.read(Path::new("c.cfg")).unwrap_err().kind() == std::io::ErrorKind::NotFound);
+		assert!(fs.read_dir(Path::new("")).unwrap() == [Path::new("a.cfg"), Path::new("b.cfg")]);
+		match fs.open(Path::new("a.cfg")).unwrap() {
+			OverlayFile::Upper(_) => (),
+			other => panic!("expected upper file, got {:?}", other),
+		}
+	}
+}
@@ -21,156 +21,3476 @@
 // OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use std::fs::File;
+use std::cell::{OnceCell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
 use std::io::Read;
+use std::ops::Range;
+use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 
+use crate::compression;
+use crate::encoding::{self, OffsetMap};
+use crate::file_stamp::FileStamp;
+use crate::file_system::remove_cur_dir;
+use crate::glob::Glob;
+use crate::line_index::LineIndex;
+use crate::source_map::{self, Segment, SourceMap};
 use crate::BorrowSlice;
+use crate::Compression;
+use crate::Encoding;
+use crate::FileHandle;
+use crate::FileSystem;
+use crate::LineEndings;
 use crate::Slice;
 use crate::SliceTracker;
+use crate::SourceMapBuilder;
+use crate::StableBorrow;
+use crate::StdFileSystem;
 
+/// Location of a slice of data.
+///
+/// With the `serde` feature enabled, this type implements `Serialize`.
+/// Expansion locations can not be serialized directly,
+/// use `FileTracker::serializable_location` to serialize them as the resolved location instead.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(bound = ""))]
 pub enum SourceLocation<'a, T: ?Sized> {
 	/// The source of the data is unknown.
 	Unknown,
 
 	/// The data was expanded from other data.
+	#[cfg_attr(feature = "serde", serde(skip))]
 	ExpandedFrom(&'a T),
 
-	/// The data came from a file.
+	/// The data was produced by an expansion, such as a macro invocation.
+	///
+	/// Use [`FileTracker::resolve_source_location_with_backtrace`] to get the locations of all call sites.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	Expansion(Expansion<'a, T>),
+
+	/// The data was copied from other data, see [`SourceMapBuilder`].
+	///
+	/// Holds the slice of the other data that the data was copied from.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	MappedFrom(&'a T),
+
+	/// The data came from a text file.
 	File(FileLocation<'a>),
+
+	/// The data was generated by the program rather than read from a file.
+	Generated(GeneratedLocation<'a>),
+
+	/// The data came from a binary file.
+	///
+	/// Line and column numbers are meaningless for binary data,
+	/// so the location is reported as byte offset in the file.
+	/// See [`FileData::LINE_LOCATIONS`] for which data this applies to.
+	FileOffset(FileOffset<'a>),
+
+	/// The data came from a file that was included by other data.
+	///
+	/// Holds the location in the included file and the whole include chain,
+	/// from the innermost include directive to the outermost.
+	/// The location in the included file is a [`SourceLocation::File`] or [`SourceLocation::FileOffset`].
+	IncludedFrom(Box<SourceLocation<'a, T>>, Vec<IncludeFrame<'a, T>>),
+}
+
+/// An include directive in the include chain of a slice of data.
+///
+/// See [`SourceLocation::IncludedFrom`].
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(bound = ""))]
+pub struct IncludeFrame<'a, T: ?Sized> {
+	/// The include directive.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	pub directive: &'a T,
+
+	/// The resolved source location of the include directive.
+	///
+	/// This is never [`SourceLocation::ExpandedFrom`], [`SourceLocation::Expansion`], [`SourceLocation::MappedFrom`]
+	/// or [`SourceLocation::IncludedFrom`].
+	pub location: SourceLocation<'a, T>,
+}
+
+/// An expansion that produced a slice of data, such as a macro invocation.
+#[derive(Debug)]
+pub struct Expansion<'a, T: ?Sized> {
+	/// The data the expansion was defined by, such as the body of a macro.
+	pub definition: &'a T,
+
+	/// The data that invoked the expansion, such as a macro call.
+	pub invocation: &'a T,
+
+	/// A label for the expansion, such as the name of a macro.
+	pub label: Option<&'a str>,
+}
+
+/// The resolved source location of a slice of data, together with the backtrace of expansion call sites.
+///
+/// See [`FileTracker::resolve_source_location_with_backtrace`].
+#[derive(Debug)]
+pub struct ResolvedLocation<'a, T: ?Sized> {
+	/// The resolved source location of the data, the same as returned by [`FileTracker::resolve_source_location`].
+	pub location: SourceLocation<'a, T>,
+
+	/// A frame for each expansion the data was produced by, from the innermost to the outermost.
+	///
+	/// This is empty if the data was not produced by an expansion.
+	pub backtrace: Vec<ExpansionFrame<'a, T>>,
+}
+
+/// A call site in the expansion backtrace of a slice of data.
+///
+/// See [`ResolvedLocation`].
+#[derive(Debug)]
+pub struct ExpansionFrame<'a, T: ?Sized> {
+	/// The label of the expansion that was invoked, such as the name of a macro.
+	pub label: Option<&'a str>,
+
+	/// The data that invoked the expansion.
+	pub invocation: &'a T,
+
+	/// The resolved source location of the invocation.
+	///
+	/// This is never [`SourceLocation::ExpandedFrom`], [`SourceLocation::Expansion`] or [`SourceLocation::MappedFrom`].
+	pub location: SourceLocation<'a, T>,
 }
 
 /// File location indicating the source of a slice of data.
+///
+/// The [`Display`](std::fmt::Display) implementation formats the location as `display_path:line:column`.
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct FileLocation<'a> {
+	/// The path of the file as it was inserted, for opening the file.
+	pub path: &'a Path,
+
+	/// The path of the file to show to users.
+	///
+	/// See [`FileTracker::add_root`] for how this path is chosen.
+	pub display_path: &'a Path,
+
+	pub line: usize,
+	pub column: usize,
+
+	/// The byte offset in the original file.
+	///
+	/// This can differ from the offset in the tracked data if the file was decoded.
+	/// For compressed files, this is the offset in the decompressed contents.
+	pub offset: usize,
+
+	/// The path as it is shared with the tracker, so it can be converted to an [`OwnedFileLocation`] without copying.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	shared_path: &'a Arc<Path>,
+}
+
+/// Byte offset in a file indicating the source of a slice of binary data.
+///
+/// The [`Display`](std::fmt::Display) implementation formats the location as `display_path:0x1a3f`.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FileOffset<'a> {
+	/// The path of the file as it was inserted, for opening the file.
 	pub path: &'a Path,
+
+	/// The path of the file to show to users.
+	///
+	/// See [`FileTracker::add_root`] for how this path is chosen.
+	pub display_path: &'a Path,
+
+	/// The byte offset in the file.
+	///
+	/// For compressed files, this is the offset in the decompressed contents.
+	pub offset: usize,
+
+	/// The path as it is shared with the tracker, so it can be converted to an [`OwnedFileOffset`] without copying.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	shared_path: &'a Arc<Path>,
+}
+
+/// Location in generated data, such as a command line argument or built-in text.
+///
+/// The [`Display`](std::fmt::Display) implementation formats the location as `<name>:line:column`.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GeneratedLocation<'a> {
+	/// The name describing the generated data.
+	pub name: &'a str,
+
+	pub line: usize,
+	pub column: usize,
+
+	/// The byte offset in the generated data.
+	pub offset: usize,
+}
+
+/// File location that shares ownership of the path.
+///
+/// Unlike a [`FileLocation`], this does not borrow from the tracker,
+/// so it can be stored in error values that outlive the tracker or cross thread boundaries.
+/// With the `serde` feature enabled, it can be used to deserialize a serialized [`FileLocation`].
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedFileLocation {
+	pub path: Arc<Path>,
+	pub display_path: Arc<Path>,
 	pub line: usize,
 	pub column: usize,
+	pub offset: usize,
+}
+
+/// Byte offset in a file that shares ownership of the path.
+///
+/// Unlike a [`FileOffset`], this does not borrow from the tracker,
+/// so it can be stored in error values that outlive the tracker or cross thread boundaries.
+/// With the `serde` feature enabled, it can be used to deserialize a serialized [`FileOffset`].
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedFileOffset {
+	pub path: Arc<Path>,
+	pub display_path: Arc<Path>,
+	pub offset: usize,
+}
+
+/// Serializable source location of a slice of tracked data.
+///
+/// Expansions and copied data are serialized as the resolved location of the original data,
+/// the same as returned by [`FileTracker::resolve_source_location`].
+/// Serialization fails if the expansions form a cycle.
+///
+/// See `FileTracker::serializable_location`.
+#[cfg(feature = "serde")]
+pub struct SerializableLocation<'a, Data: FileData, Fs: FileSystem = StdFileSystem> {
+	tracker: &'a FileTracker<Data, Fs>,
+	data: &'a Data::Slice,
+}
+
+#[cfg(feature = "serde")]
+impl<Data: FileData, Fs: FileSystem> serde::Serialize for SerializableLocation<'_, Data, Fs> {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self.tracker.resolve_source_location(self.data) {
+			Some(location) => location.serialize(serializer),
+			None => Err(serde::ser::Error::custom("expansions form a cycle")),
+		}
+	}
 }
 
 /// Source of a slice of data.
+///
+/// With the `serde` feature enabled, this type implements `Serialize` and `Deserialize`.
+/// The [`Source::ExpandedFrom`], [`Source::Expansion`], [`Source::Mapped`] and [`Source::IncludedFrom`] variants
+/// hold raw pointers and can not be serialized.
+/// Use `FileTracker::serializable_location` to serialize the resolved location of the data instead.
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Source<T: Slice + ?Sized> {
 	/// Unknown source.
 	Unknown,
 
 	/// The data was expanded from other data.
+	#[cfg_attr(feature = "serde", serde(skip))]
 	ExpandedFrom(*const T::Element, usize),
 
+	/// The data was produced by an expansion, such as a macro invocation.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	Expansion {
+		/// The data the expansion was defined by, as start pointer and length of the tracked slice.
+		definition: (*const T::Element, usize),
+
+		/// The data that invoked the expansion, as start pointer and length of the tracked slice.
+		invocation: (*const T::Element, usize),
+
+		/// A label for the expansion, such as the name of a macro.
+		label: Option<String>,
+	},
+
+	/// The data was built from fragments of other data, see [`SourceMapBuilder`].
+	#[cfg_attr(feature = "serde", serde(skip))]
+	Mapped {
+		/// The fragments of the data, as offset in the data and start pointer and length of the tracked slice.
+		///
+		/// The fragments are sorted by offset and do not overlap.
+		fragments: Vec<(usize, (*const T::Element, usize))>,
+	},
+
 	/// The data was read from a file.
-	File(PathBuf),
+	File {
+		/// The path of the file.
+		path: Arc<Path>,
+
+		/// The encoding the file was decoded from, or `None` if the data holds the raw contents of the file.
+		encoding: Option<Encoding>,
+
+		/// The compression the file was decompressed from, or `None` if the file was not compressed.
+		#[cfg_attr(feature = "serde", serde(default))]
+		compression: Option<Compression>,
+	},
+
+	/// The data was generated by the program rather than read from a file.
+	///
+	/// Use this for data like command line arguments, environment variables or built-in text,
+	/// so that source locations can still refer to it by name.
+	Generated {
+		/// A short name describing the data, such as `command line` or `prelude`.
+		name: String,
+	},
+
+	/// The data was read from a file that was included by other data.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	IncludedFrom {
+		/// The path of the file.
+		path: Arc<Path>,
+
+		/// The encoding the file was decoded from, or `None` if the data holds the raw contents of the file.
+		encoding: Option<Encoding>,
+
+		/// The compression the file was decompressed from, or `None` if the file was not compressed.
+		compression: Option<Compression>,
+
+		/// The include directive, as start pointer and length of the tracked slice.
+		directive: (*const T::Element, usize),
+	},
 }
 
-/// Search for a subslice, and compute the location as (line, colum) in the larger slice.
-fn compute_location(subslice: &[u8], data: &[u8]) -> (usize, usize) {
-	let offset = subslice.as_ptr() as usize - data.as_ptr() as usize;
-	let mut line_breaks = memchr::memrchr_iter(b'\n', &data[..offset]);
-	match line_breaks.next() {
-		None => (1, offset + 1),
-		Some(i) => (line_breaks.count() + 2, offset - i),
+impl<T: Slice + ?Sized> Source<T> {
+	/// Get the path of the file the data was read from, if any.
+	fn path(&self) -> Option<&Arc<Path>> {
+		match self {
+			Source::File { path, .. } | Source::IncludedFrom { path, .. } => Some(path),
+			_ => None,
+		}
+	}
+
+	/// Get the tracked slices that the source refers to, as start pointer and length.
+	fn references(&self) -> Vec<(*const T::Element, usize)> {
+		match self {
+			Source::ExpandedFrom(start, len) => vec![(*start, *len)],
+			Source::Expansion {
+				definition, invocation, ..
+			} => vec![*definition, *invocation],
+			Source::Mapped { fragments } => fragments.iter().map(|&(_, fragment)| fragment).collect(),
+			Source::IncludedFrom { directive, .. } => vec![*directive],
+			Source::Unknown | Source::File { .. } | Source::Generated { .. } => Vec::new(),
+		}
+	}
+
+	/// Get the encoding of the file the data was read from, if any.
+	fn encoding(&self) -> Option<Encoding> {
+		match self {
+			Source::File { encoding, .. } | Source::IncludedFrom { encoding, .. } => *encoding,
+			_ => None,
+		}
+	}
+}
+
+impl<T: Slice + ?Sized> std::fmt::Debug for Source<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Source::Unknown => write!(f, "Unknown"),
+			Source::ExpandedFrom(_source, len) => f.debug_struct("ExpandedFrom").field("len", len).finish(),
+			Source::Expansion {
+				definition: (_, len),
+				label,
+				..
+			} => f
+				.debug_struct("Expansion")
+				.field("len", len)
+				.field("label", label)
+				.finish(),
+			Source::File {
+				path,
+				encoding,
+				compression,
+			} => f
+				.debug_struct("File")
+				.field("path", path)
+				.field("encoding", encoding)
+				.field("compression", compression)
+				.finish(),
+			Source::Mapped { fragments } => f.debug_struct("Mapped").field("fragments", &fragments.len()).finish(),
+			Source::Generated { name } => f.debug_struct("Generated").field("name", name).finish(),
+			Source::IncludedFrom {
+				path,
+				encoding,
+				compression,
+				..
+			} => f
+				.debug_struct("IncludedFrom")
+				.field("path", path)
+				.field("encoding", encoding)
+				.field("compression", compression)
+				.finish(),
+		}
 	}
 }
 
-/// Read a text file (UTF-8) into a string.
-fn read_text_file<P: ?Sized + AsRef<Path>>(path: &P) -> std::io::Result<String> {
-	let mut file = File::open(path)?;
-	let mut data = String::new();
-	file.read_to_string(&mut data)?;
-	return Ok(data);
+impl std::fmt::Display for FileLocation<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}:{}:{}", self.display_path.display(), self.line, self.column)
+	}
 }
 
-/// Read a binary file into a Vec<u8>.
-fn read_binary_file<P: ?Sized + AsRef<Path>>(path: &P) -> std::io::Result<Vec<u8>> {
-	let mut file = File::open(path)?;
-	let mut data = Vec::new();
-	file.read_to_end(&mut data)?;
-	return Ok(data);
+impl std::fmt::Display for GeneratedLocation<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "<{}>:{}:{}", self.name, self.line, self.column)
+	}
 }
 
-pub trait FileTracker<Data: BorrowSlice + ?Sized> {
-	/// Read a file and insert it into the tracker.
-	///
-	/// Fails if reading the file fails, or if the file is empty.
-	fn insert_file(&self, path: impl Into<PathBuf>) -> std::io::Result<&Data::Slice>;
+impl std::fmt::Display for FileOffset<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}:{:#x}", self.display_path.display(), self.offset)
+	}
+}
 
-	/// Get the source location for a slice of data.
-	fn get_source_location<'s, 'd>(&'s self, data: &'d Data::Slice) -> Option<SourceLocation<'s, Data::Slice>>;
+impl std::fmt::Debug for FileLocation<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("FileLocation")
+			.field("path", &self.path)
+			.field("display_path", &self.display_path)
+			.field("line", &self.line)
+			.field("column", &self.column)
+			.field("offset", &self.offset)
+			.finish()
+	}
 }
 
-impl FileTracker<String> for SliceTracker<String, Source<str>> {
-	fn insert_file(&self, path: impl Into<PathBuf>) -> std::io::Result<&str> {
-		let path = path.into();
-		let data = read_text_file(&path)?;
-		if data.is_empty() {
-			Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "file is empty"))
+impl FileLocation<'_> {
+	/// Convert the location to an [`OwnedFileLocation`].
+	///
+	/// This does not copy the path, it only increments the reference count of the path shared with the tracker.
+	/// The display path is only copied if it differs from the path.
+	pub fn to_owned_location(&self) -> OwnedFileLocation {
+		let display_path = if std::ptr::eq(self.display_path, self.path) {
+			self.shared_path.clone()
 		} else {
-			// New strings can't be in the tracker yet, so this should be safe.
-			Ok(unsafe { self.insert_unsafe(data, Source::File(path)) })
+			self.display_path.into()
+		};
+		OwnedFileLocation {
+			path: self.shared_path.clone(),
+			display_path,
+			line: self.line,
+			column: self.column,
+			offset: self.offset,
 		}
 	}
+}
 
-	fn get_source_location(&self, data: &str) -> Option<SourceLocation<str>> {
-		let (whole_slice, source) = self.get(data)?;
-		Some(match source {
-			Source::Unknown => SourceLocation::Unknown,
-			Source::ExpandedFrom(_source, _len) => unimplemented!(), //SourceLocation::ExpandedFrom(sources),
-			Source::File(path) => {
-				let (line, column) = compute_location(data.as_bytes(), whole_slice.as_bytes());
-				SourceLocation::File(FileLocation { path, line, column })
-			}
-		})
+impl From<FileLocation<'_>> for OwnedFileLocation {
+	fn from(other: FileLocation) -> Self {
+		other.to_owned_location()
 	}
 }
 
-impl<'a> FileTracker<Vec<u8>> for SliceTracker<Vec<u8>, Source<[u8]>> {
-	fn insert_file(&self, path: impl Into<PathBuf>) -> std::io::Result<&[u8]> {
-		let path = path.into();
-		let data = read_binary_file(&path)?;
-		if data.is_empty() {
-			Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "file is empty"))
+impl std::fmt::Display for OwnedFileLocation {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}:{}:{}", self.display_path.display(), self.line, self.column)
+	}
+}
+
+impl std::fmt::Debug for FileOffset<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("FileOffset")
+			.field("path", &self.path)
+			.field("display_path", &self.display_path)
+			.field("offset", &self.offset)
+			.finish()
+	}
+}
+
+impl FileOffset<'_> {
+	/// Convert the location to an [`OwnedFileOffset`].
+	///
+	/// This does not copy the path, it only increments the reference count of the path shared with the tracker.
+	/// The display path is only copied if it differs from the path.
+	pub fn to_owned_location(&self) -> OwnedFileOffset {
+		let display_path = if std::ptr::eq(self.display_path, self.path) {
+			self.shared_path.clone()
 		} else {
-			// New strings can't be in the tracker yet, so this should be safe.
-			Ok(unsafe { self.insert_unsafe(data, Source::File(path)) })
+			self.display_path.into()
+		};
+		OwnedFileOffset {
+			path: self.shared_path.clone(),
+			display_path,
+			offset: self.offset,
 		}
 	}
+}
 
-	fn get_source_location(&self, data: &[u8]) -> Option<SourceLocation<[u8]>> {
-		let (whole_slice, source) = self.get(data)?;
-		Some(match source {
-			Source::Unknown => SourceLocation::Unknown,
-			Source::ExpandedFrom(_source, _len) => unimplemented!(), //SourceLocation::ExpandedFrom(sources),
-			Source::File(path) => {
-				let (line, column) = compute_location(data, whole_slice);
-				SourceLocation::File(FileLocation { path, line, column })
-			}
+impl From<FileOffset<'_>> for OwnedFileOffset {
+	fn from(other: FileOffset) -> Self {
+		other.to_owned_location()
+	}
+}
+
+impl std::fmt::Display for OwnedFileOffset {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}:{:#x}", self.display_path.display(), self.offset)
+	}
+}
+
+/// Compute the range of a subslice given as start pointer and length in a larger slice.
+///
+/// The subslice must be contained in the larger slice.
+fn subslice_range(start: *const u8, len: usize, data: &[u8]) -> Range<usize> {
+	let offset = start as usize - data.as_ptr() as usize;
+	offset..offset + len
+}
+
+/// Check if a relative path stays inside the directory it is relative to.
+///
+/// Returns `false` if the path is absolute, or if `..` components lead out of the directory.
+fn is_contained(path: &Path) -> bool {
+	let mut depth = 0usize;
+	for component in path.components() {
+		match component {
+			Component::Prefix(_) | Component::RootDir => return false,
+			Component::CurDir => (),
+			Component::ParentDir => match depth.checked_sub(1) {
+				Some(parent) => depth = parent,
+				None => return false,
+			},
+			Component::Normal(_) => depth += 1,
+		}
+	}
+	true
+}
+
+/// Compute the offset of a subslice in a larger slice.
+fn offset_in(subslice: &[u8], data: &[u8]) -> usize {
+	subslice.as_ptr() as usize - data.as_ptr() as usize
+}
+
+/// Search for a subslice, and compute the location as (line, colum) in the larger slice.
+fn compute_location(subslice: &[u8], data: &[u8], lines: &LineIndex) -> (usize, usize) {
+	lines.location(offset_in(subslice, data))
+}
+
+/// Read a binary file into a Vec<u8>, together with the state of the file when it was read.
+///
+/// Compressed files are decompressed, see [`compression::decompress`].
+/// The compression is only detected by the contents of the file if `detect_contents` is true.
+///
+/// The metadata is taken from the opened file before reading it,
+/// so that changes made while the file is read are detected later.
+fn read_binary_file(file_system: &impl FileSystem, path: &Path, detect_contents: bool) -> BinaryReadResult {
+	let mut file = file_system.open(path)?;
+	let metadata = file.metadata()?;
+	let mut data = Vec::with_capacity(usize::try_from(metadata.len).unwrap_or(0));
+	file.read_to_end(&mut data)?;
+	let stamp = FileStamp::new(&metadata, &data);
+	let (data, compression) = compression::decompress(path, data, detect_contents, Compression::MAX_DECOMPRESSED_SIZE)?;
+	Ok((data, compression, stamp))
+}
+
+/// Read a file asynchronously from the standard file system, along with its current state.
+#[cfg(feature = "tokio")]
+async fn read_binary_file_async(path: &Path, detect_contents: bool) -> BinaryReadResult {
+	use tokio::io::AsyncReadExt;
+	let mut file = tokio::fs::File::open(path).await?;
+	let metadata: crate::FileMetadata = file.metadata().await?.into();
+	let mut data = Vec::with_capacity(usize::try_from(metadata.len).unwrap_or(0));
+	file.read_to_end(&mut data).await?;
+	let stamp = FileStamp::new(&metadata, &data);
+	let (data, compression) = compression::decompress(path, data, detect_contents, Compression::MAX_DECOMPRESSED_SIZE)?;
+	Ok((data, compression, stamp))
+}
+
+/// Read a file and decode it with [`FileData::decode`].
+fn read_file<Data: FileData, Fs: FileSystem>(
+	file_system: &Fs,
+	path: &Path,
+	_encoding: Option<Encoding>,
+) -> ReadResult<Data> {
+	let (data, compression, stamp) = read_binary_file(file_system, path, Data::DETECT_COMPRESSION)?;
+	let (data, encoding, offsets) = Data::decode(data)?;
+	Ok((data, encoding, compression, offsets, stamp))
+}
+
+/// The decompressed contents, compression and state of a file that was read.
+type BinaryReadResult = std::io::Result<(Vec<u8>, Option<Compression>, FileStamp)>;
+
+/// The decoded data, encoding, compression, offset map and state of a file that was read.
+type ReadResult<Data> = std::io::Result<(Data, Option<Encoding>, Option<Compression>, OffsetMap, FileStamp)>;
+
+/// The path of each file visited by [`FileTracker::insert_dir`], with the result of inserting it.
+type InsertDirResults<'a, Slice> = Vec<(PathBuf, std::io::Result<&'a Slice>)>;
+
+/// Function to read a file, given the encoding that was requested when the file was inserted.
+type ReadFile<Data, Fs> = fn(&Fs, &Path, Option<Encoding>) -> ReadResult<Data>;
+
+/// How a tracked file was read, used to detect changes and to read it again.
+struct FileReader<Data, Fs> {
+	/// The function used to read the file.
+	read: ReadFile<Data, Fs>,
+
+	/// The encoding that was requested when the file was inserted.
+	encoding: Option<Encoding>,
+
+	/// The state of the file when it was read.
+	stamp: FileStamp,
+}
+
+/// A tracked file in the path index.
+struct IndexedFile<Data, Fs> {
+	/// The path of the file as it was inserted.
+	path: Arc<Path>,
+
+	/// The start pointer and length of the data.
+	///
+	/// The pointer is never dereferenced, it is only used to look up the data in the tracker.
+	/// Tracked data is only removed by [`FileTracker::reload`], which updates the index too,
+	/// so the pointer always refers to the data of this file.
+	data: (*const u8, usize),
+
+	/// How the file was read, or `None` if the data was not read from the file by the tracker.
+	reader: Option<FileReader<Data, Fs>>,
+}
+
+impl<Data, Fs> IndexedFile<Data, Fs> {
+	/// Get the reader of the file.
+	///
+	/// Fails if the data was not read from the file by the tracker.
+	fn reader(&self) -> std::io::Result<&FileReader<Data, Fs>> {
+		self.reader.as_ref().ok_or_else(|| {
+			let message = format!("file was not read by the tracker: {}", self.path.display());
+			std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
 		})
 	}
 }
 
-#[cfg(test)]
-mod test {
-	use super::*;
-	use assert2::assert;
+/// Get a tracked file from the path index.
+///
+/// The path must already be normalized with [`normalize_path`].
+/// Fails if the file is not tracked.
+fn indexed_file<'a, Data, Fs>(
+	paths: &'a BTreeMap<PathBuf, IndexedFile<Data, Fs>>,
+	path: &Path,
+) -> std::io::Result<&'a IndexedFile<Data, Fs>> {
+	paths.get(path).ok_or_else(|| {
+		let message = format!("file not tracked: {}", path.display());
+		std::io::Error::new(std::io::ErrorKind::NotFound, message)
+	})
+}
 
-	#[test]
-	fn test_compute_location() {
-		let data = b"hello\nworld";
+/// The old and new data of a file reloaded with [`FileTracker::reload`].
+pub struct Reloaded<'a, Data: FileData> {
+	/// The old data, which is no longer tracked.
+	pub old_data: Data,
 
-		assert!(compute_location(&data[0..], data) == (1, 1));
-		assert!(compute_location(&data[1..], data) == (1, 2));
-		assert!(compute_location(&data[2..], data) == (1, 3));
-		assert!(compute_location(&data[3..], data) == (1, 4));
-		assert!(compute_location(&data[4..], data) == (1, 5));
-		assert!(compute_location(&data[5..], data) == (1, 6));
-		assert!(compute_location(&data[6..], data) == (2, 1));
-		assert!(compute_location(&data[7..], data) == (2, 2));
+	/// The source of the old data.
+	pub old_source: Source<Data::Slice>,
 
-		let data = b"a\r\na\n";
-		assert!(compute_location(&data[0..], data) == (1, 1));
-		assert!(compute_location(&data[1..], data) == (1, 2));
-		assert!(compute_location(&data[2..], data) == (1, 3));
-		assert!(compute_location(&data[3..], data) == (2, 1));
-		assert!(compute_location(&data[4..], data) == (2, 2));
+	/// The new data, which is tracked in place of the old data.
+	pub data: &'a Data::Slice,
+}
+
+impl<Data: FileData> std::fmt::Debug for Reloaded<'_, Data> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("Reloaded")
+			.field("old_source", &self.old_source)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Glob filters for [`FileTracker::insert_dir`].
+struct DirFilter {
+	/// Files must match one of these globs, unless there are none.
+	include: Vec<Glob>,
+
+	/// Files and directories matching any of these globs are skipped.
+	exclude: Vec<Glob>,
+}
+
+/// Normalize a path for the path index.
+///
+/// The path is normalized lexically, without accessing the file system:
+/// `.` components are removed, and `..` components remove the component before them.
+/// If `case_insensitive` is true, the path is converted to lowercase too.
+fn normalize_path(path: &Path, case_insensitive: bool) -> PathBuf {
+	let mut normalized = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::CurDir => (),
+			Component::ParentDir => match normalized.components().next_back() {
+				Some(Component::Normal(_)) => {
+					normalized.pop();
+				}
+				Some(Component::RootDir) | Some(Component::Prefix(_)) => (),
+				Some(Component::ParentDir) | Some(Component::CurDir) | None => normalized.push(".."),
+			},
+			component => normalized.push(component),
+		}
+	}
+	if case_insensitive {
+		normalized.to_string_lossy().to_lowercase().into()
+	} else {
+		normalized
+	}
+}
+
+/// Data that can be read from a file and tracked by a [`FileTracker`].
+pub trait FileData: BorrowSlice + StableBorrow + Sized {
+	/// Whether source locations in the data are reported as line and column.
+	///
+	/// Otherwise, source locations are reported as byte offset in the file, see [`SourceLocation::FileOffset`].
+	/// This is `true` for text data such as `String`, and `false` for all binary data such as `Vec<u8>`,
+	/// so that the same bytes are reported the same way regardless of how they are stored.
+	const LINE_LOCATIONS: bool;
+
+	/// Whether compressed files are detected by their magic bytes, in addition to their extension.
+	///
+	/// This should be `false` for binary data, which can start with the magic bytes of a compression format by chance.
+	/// Such files are then only decompressed if they have the extension of the compression format.
+	const DETECT_COMPRESSION: bool;
+
+	/// Decode the contents of a file.
+	///
+	/// Returns the decoded data, the encoding of the file
+	/// and the mapping from offsets in the decoded data to offsets in the file.
+	/// The encoding is `None` if the data holds the raw contents of the file.
+	fn decode(data: Vec<u8>) -> std::io::Result<(Self, Option<Encoding>, OffsetMap)>;
+
+	/// Get the raw bytes of a slice.
+	fn as_bytes(slice: &Self::Slice) -> &[u8];
+
+	/// Get a subslice of a slice by byte range.
+	///
+	/// Returns `None` if the range is out of bounds or does not form a valid subslice.
+	fn get_subslice(slice: &Self::Slice, range: Range<usize>) -> Option<&Self::Slice>;
+}
+
+impl FileData for String {
+	const LINE_LOCATIONS: bool = true;
+	const DETECT_COMPRESSION: bool = true;
+
+	fn decode(data: Vec<u8>) -> std::io::Result<(Self, Option<Encoding>, OffsetMap)> {
+		let (data, encoding, offsets) = encoding::decode(data, None, false)?;
+		Ok((data, Some(encoding), offsets))
+	}
+
+	fn as_bytes(slice: &str) -> &[u8] {
+		slice.as_bytes()
+	}
+
+	fn get_subslice(slice: &str, range: Range<usize>) -> Option<&str> {
+		slice.get(range)
+	}
+}
+
+impl FileData for Vec<u8> {
+	const LINE_LOCATIONS: bool = false;
+	const DETECT_COMPRESSION: bool = false;
+
+	fn decode(data: Vec<u8>) -> std::io::Result<(Self, Option<Encoding>, OffsetMap)> {
+		Ok((data, None, OffsetMap::identity()))
+	}
+
+	fn as_bytes(slice: &[u8]) -> &[u8] {
+		slice
+	}
+
+	fn get_subslice(slice: &[u8], range: Range<usize>) -> Option<&[u8]> {
+		slice.get(range)
+	}
+}
+
+#[cfg(feature = "mmap")]
+impl FileData for memmap2::Mmap {
+	const LINE_LOCATIONS: bool = false;
+	const DETECT_COMPRESSION: bool = false;
+
+	/// Copy the contents of a file into an anonymous memory map.
+	///
+	/// Use [`FileTracker::insert_file_mmap`] to map a file directly.
+	fn decode(data: Vec<u8>) -> std::io::Result<(Self, Option<Encoding>, OffsetMap)> {
+		let mut map = memmap2::MmapMut::map_anon(data.len())?;
+		map.copy_from_slice(&data);
+		Ok((map.make_read_only()?, None, OffsetMap::identity()))
+	}
+
+	fn as_bytes(slice: &[u8]) -> &[u8] {
+		slice
+	}
+
+	fn get_subslice(slice: &[u8], range: Range<usize>) -> Option<&[u8]> {
+		slice.get(range)
+	}
+}
+
+/// Metadata for a tracked slice.
+struct FileEntry<T: Slice + ?Sized> {
+	/// The source of the data.
+	source: Source<T>,
+
+	/// The line index of the data, computed when first needed.
+	lines: OnceCell<LineIndex>,
+
+	/// Mapping from offsets in the data to offsets in the original file.
+	offsets: OffsetMap,
+
+	/// The absolute path of the file, if the data came from a file.
+	///
+	/// This is resolved when the data is inserted, so later changes of the working directory do not affect it.
+	absolute_path: Option<PathBuf>,
+}
+
+impl<T: Slice + ?Sized> FileEntry<T> {
+	fn new(source: Source<T>, offsets: OffsetMap) -> Self {
+		let absolute_path = source.path().and_then(|path| std::path::absolute(path).ok());
+		Self {
+			source,
+			lines: OnceCell::new(),
+			offsets,
+			absolute_path,
+		}
+	}
+
+	/// Get the line index for the data of this entry.
+	fn lines(&self, data: &[u8], line_endings: LineEndings) -> &LineIndex {
+		self.lines.get_or_init(|| LineIndex::new(data, line_endings))
+	}
+
+	/// Compute the location of a subslice as (line, column) in the data of this entry.
+	///
+	/// For UTF-8 files, the column is counted in bytes of the original file,
+	/// so that it still matches the file if invalid data was replaced while decoding.
+	/// For other encodings, the column is counted in bytes of the decoded data.
+	fn location(&self, subslice: &[u8], data: &[u8], line_endings: LineEndings) -> (usize, usize) {
+		let (line, column) = compute_location(subslice, data, self.lines(data, line_endings));
+		let offset = offset_in(subslice, data);
+		let line_start = offset + 1 - column;
+		(line, self.column_offset(offset) - self.column_offset(line_start) + 1)
+	}
+
+	/// Map a byte offset in the data to the offset that columns are counted in, see [`Self::location`].
+	fn column_offset(&self, offset: usize) -> usize {
+		match self.source.encoding() {
+			Some(Encoding::Utf8) => self.offsets.file_offset(offset),
+			_ => offset,
+		}
+	}
+
+	/// Map an offset that columns are counted in back to a byte offset in the data.
+	///
+	/// This is the reverse of [`Self::column_offset`].
+	fn data_offset(&self, offset: usize) -> usize {
+		match self.source.encoding() {
+			Some(Encoding::Utf8) => self.offsets.data_offset(offset),
+			_ => offset,
+		}
+	}
+}
+
+impl<T: Slice + ?Sized> std::fmt::Debug for FileEntry<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		self.source.fmt(f)
+	}
+}
+
+/// Tracker for data read from files.
+///
+/// The tracker can map slices of the data back to the file they came from,
+/// and it can look up slices of tracked files by path, line and column.
+///
+/// Files are read from a [`FileSystem`], which is the real file system by default.
+///
+/// Before version 0.2, `FileTracker` was a trait implemented for `SliceTracker<String, Source<str>>`
+/// and `SliceTracker<Vec<u8>, Source<[u8]>>`.
+/// Looking up slices by path needs an index of the tracked files next to the tracked data,
+/// which a `SliceTracker` with a [`Source`] as metadata can not hold, so it is a struct now.
+/// Replace `SliceTracker::<String, Source<str>>::new()` with `FileTracker::<String>::new()`;
+/// [`Self::insert_file`] and [`Self::get_source_location`] work the same as before.
+pub struct FileTracker<Data: FileData, Fs: FileSystem = StdFileSystem> {
+	/// The tracked data.
+	tracker: SliceTracker<Data, FileEntry<Data::Slice>>,
+
+	/// Sentinels for empty files.
+	///
+	/// Each empty file is tracked as the empty slice in the middle of a two byte sentinel.
+	/// Unlike the end of other data, this position can not be the start of another slice.
+	empty_files: SliceTracker<Data, FileEntry<Data::Slice>>,
+
+	/// Index of the tracked files by normalized path.
+	paths: RefCell<BTreeMap<PathBuf, IndexedFile<Data, Fs>>>,
+
+	/// The line terminators that separate lines.
+	line_endings: LineEndings,
+
+	/// Whether paths are matched case-insensitively when looking up files.
+	case_insensitive_paths: bool,
+
+	/// Absolute root directories to show paths relative to.
+	roots: Vec<PathBuf>,
+
+	/// The file system that files are read from.
+	file_system: Fs,
+}
+
+impl<Data: FileData, Fs: FileSystem + Default> Default for FileTracker<Data, Fs> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<Data: FileData, Fs: FileSystem + Default> FileTracker<Data, Fs> {
+	/// Create a new file tracker.
+	///
+	/// The tracker recognizes both `"\n"` and `"\r\n"` as line terminators.
+	pub fn new() -> Self {
+		Self::with_line_endings(LineEndings::default())
+	}
+
+	/// Create a new file tracker that recognizes the given line terminators.
+	pub fn with_line_endings(line_endings: LineEndings) -> Self {
+		Self::with_file_system(Fs::default(), line_endings)
+	}
+}
+
+impl<Data: FileData, Fs: FileSystem> FileTracker<Data, Fs> {
+	/// Create a new file tracker that reads files from the given file system.
+	pub fn with_file_system(file_system: Fs, line_endings: LineEndings) -> Self {
+		Self {
+			tracker: SliceTracker::new(),
+			empty_files: SliceTracker::new(),
+			paths: RefCell::new(BTreeMap::new()),
+			line_endings,
+			case_insensitive_paths: false,
+			roots: Vec::new(),
+			file_system,
+		}
+	}
+
+	/// Get the file system that files are read from.
+	pub fn file_system(&self) -> &Fs {
+		&self.file_system
+	}
+
+	/// Get the file system that files are read from as mutable reference.
+	///
+	/// Changes to the file system do not affect the tracked data until files are reloaded with [`Self::reload`].
+	pub fn file_system_mut(&mut self) -> &mut Fs {
+		&mut self.file_system
+	}
+
+	/// Get the line terminators recognized by the tracker.
+	pub fn line_endings(&self) -> LineEndings {
+		self.line_endings
+	}
+
+	/// Check if paths are matched case-insensitively when looking up files.
+	pub fn case_insensitive_paths(&self) -> bool {
+		self.case_insensitive_paths
+	}
+
+	/// Set whether paths are matched case-insensitively when looking up files.
+	///
+	/// Paths are normalized lexically before they are compared, so that `./a.cfg`, `sub/../a.cfg` and `a.cfg` refer to the same file.
+	/// The file system is not accessed to look up a file, so symbolic links are not resolved,
+	/// and a relative path does not refer to the same file as an absolute path.
+	/// With case-insensitive matching, `A.cfg` refers to the same file too.
+	/// If multiple tracked files map to the same path after this change, only one of them is kept in the index.
+	pub fn set_case_insensitive_paths(&mut self, case_insensitive: bool) {
+		self.case_insensitive_paths = case_insensitive;
+		let paths = std::mem::take(self.paths.get_mut());
+		for file in paths.into_values() {
+			let key = normalize_path(&file.path, case_insensitive);
+			self.paths.get_mut().insert(key, file);
+		}
+	}
+
+	/// Add a root directory to show paths in source locations relative to.
+	///
+	/// Paths in source locations are shown relative to the longest root directory that contains the file.
+	/// If no root directory contains the file, its absolute path is shown instead.
+	/// Without root directories, paths are shown as they were inserted.
+	/// The path as it was inserted is always available as [`FileLocation::path`].
+	///
+	/// Relative root directories are resolved against the current working directory.
+	/// Files are compared against the roots by their absolute path, without resolving symbolic links.
+	///
+	/// Fails if the root directory can not be made absolute.
+	pub fn add_root(&mut self, root: impl AsRef<Path>) -> std::io::Result<()> {
+		self.roots.push(std::path::absolute(root)?);
+		Ok(())
+	}
+
+	/// Get the root directories that paths in source locations are shown relative to.
+	pub fn roots(&self) -> &[PathBuf] {
+		&self.roots
+	}
+
+	/// Read a file and insert it into the tracker.
+	///
+	/// Text files are decoded according to their byte order mark,
+	/// and must be valid UTF-8 if they do not have one.
+	/// Binary files are inserted as they are.
+	///
+	/// With the `gzip` feature enabled, files that have a `.gz` extension are decompressed before they are decoded.
+	/// Text files that start with the gzip magic bytes are decompressed too,
+	/// but binary data is only decompressed based on the extension, see [`FileData::DETECT_COMPRESSION`].
+	/// Files that decompress to more than [`Compression::MAX_DECOMPRESSED_SIZE`] bytes are rejected.
+	/// Source locations then refer to the decompressed contents.
+	///
+	/// Empty files are tracked too.
+	/// The returned empty slice can be used to look up the file, and it is located at line 1, column 1.
+	///
+	/// Fails if reading or decoding the file fails.
+	pub fn insert_file(&self, path: impl Into<PathBuf>) -> std::io::Result<&Data::Slice> {
+		self.insert_read(path.into().into(), None, read_file, None)
+	}
+
+	/// Read a file that was included by other data and insert it into the tracker.
+	///
+	/// The file is read in the same way as by [`Self::insert_file`].
+	/// The include directive must be a tracked slice.
+	/// Source locations in the file are reported as [`SourceLocation::IncludedFrom`] the include directive.
+	///
+	/// Fails if the include directive is not tracked, or if reading or decoding the file fails.
+	pub fn insert_included_file(
+		&self,
+		path: impl Into<PathBuf>,
+		directive: &Data::Slice,
+	) -> std::io::Result<&Data::Slice> {
+		use std::io::{Error, ErrorKind};
+
+		if !self.is_tracked(directive) {
+			return Err(Error::new(ErrorKind::InvalidInput, "include directive is not tracked"));
+		}
+		self.insert_read(path.into().into(), Some(directive), read_file, None)
+	}
+
+	/// Read a file with the given function and insert it into the tracker.
+	///
+	/// If an include directive is given, the file is tracked as included from the directive.
+	fn insert_read(
+		&self,
+		path: Arc<Path>,
+		directive: Option<&Data::Slice>,
+		read: ReadFile<Data, Fs>,
+		requested_encoding: Option<Encoding>,
+	) -> std::io::Result<&Data::Slice> {
+		let (data, encoding, compression, offsets, stamp) = read(&self.file_system, &path, requested_encoding)?;
+		let source = match directive {
+			None => Source::File {
+				path,
+				encoding,
+				compression,
+			},
+			Some(directive) => Source::IncludedFrom {
+				path,
+				encoding,
+				compression,
+				directive: (directive.start_ptr(), directive.len()),
+			},
+		};
+		let reader = FileReader {
+			read,
+			encoding: requested_encoding,
+			stamp,
+		};
+		self.insert_decoded(source, data, offsets, Some(reader))
+	}
+
+	/// Insert the decoded contents of a file.
+	///
+	/// The source must have a path.
+	fn insert_decoded(
+		&self,
+		source: Source<Data::Slice>,
+		data: Data,
+		offsets: OffsetMap,
+		reader: Option<FileReader<Data, Fs>>,
+	) -> std::io::Result<&Data::Slice> {
+		let path = source.path().cloned();
+		let entry = FileEntry::new(source, offsets);
+		let data = if data.is_empty() {
+			let (sentinel, _, _) = Data::decode(vec![0, 0])?;
+			// New data can't be in the tracker yet, so this should be safe.
+			let sentinel = unsafe { self.empty_files.insert_unsafe(sentinel, entry) };
+			Data::get_subslice(sentinel, 1..1).unwrap()
+		} else {
+			// New data can't be in the tracker yet, so this should be safe.
+			unsafe { self.tracker.insert_unsafe(data, entry) }
+		};
+		if let Some(path) = path {
+			self.index_path(path, data, reader);
+		}
+		Ok(data)
+	}
+
+	/// Safely insert data with a source.
+	///
+	/// Fails if the data is empty or if it is already (partially) tracked.
+	#[allow(clippy::result_unit_err)]
+	pub fn insert(&self, data: Data, source: Source<Data::Slice>) -> Result<&Data::Slice, ()> {
+		let path = source.path().cloned();
+		let data = self
+			.tracker
+			.insert(data, FileEntry::new(source, OffsetMap::identity()))?;
+		if let Some(path) = path {
+			self.index_path(path, data, None);
+		}
+		Ok(data)
+	}
+
+	/// Insert data that was produced by an expansion, such as a macro invocation.
+	///
+	/// The definition is the data the expansion was defined by, such as the body of a macro,
+	/// and the invocation is the data that invoked the expansion, such as the macro call.
+	/// Both must be tracked slices.
+	/// The label can be used to describe the expansion, such as with the name of a macro.
+	///
+	/// Source locations in the data are reported as [`SourceLocation::Expansion`].
+	/// Use [`Self::resolve_source_location_with_backtrace`] to get the locations of the definition and all call sites.
+	///
+	/// Fails if the definition or invocation is not tracked, or if the data is empty or already (partially) tracked.
+	pub fn insert_expansion(
+		&self,
+		data: Data,
+		definition: &Data::Slice,
+		invocation: &Data::Slice,
+		label: Option<&str>,
+	) -> std::io::Result<&Data::Slice> {
+		use std::io::{Error, ErrorKind};
+
+		if !self.is_tracked(definition) {
+			return Err(Error::new(
+				ErrorKind::InvalidInput,
+				"expansion definition is not tracked",
+			));
+		}
+		if !self.is_tracked(invocation) {
+			return Err(Error::new(
+				ErrorKind::InvalidInput,
+				"expansion invocation is not tracked",
+			));
+		}
+		let source = Source::Expansion {
+			definition: (definition.start_ptr(), definition.len()),
+			invocation: (invocation.start_ptr(), invocation.len()),
+			label: label.map(String::from),
+		};
+		self.insert(data, source)
+			.map_err(|()| Error::new(ErrorKind::InvalidInput, "data is empty or already tracked"))
+	}
+
+	/// Read all files in a directory tree and insert them into the tracker.
+	///
+	/// Files are inserted with [`Self::insert_file_once`] if their path relative to `root` matches any of the include globs,
+	/// or if there are no include globs.
+	/// Files and directories matching any of the exclude globs are skipped.
+	/// Globs match the whole relative path with `/` as separator, so use `**/*.cfg` to match files in subdirectories too.
+	/// In globs, `*` and `?` match any sequence of characters or any single character within a path component,
+	/// `[a-z]` and `[!a-z]` match a single character from or not from a set,
+	/// and `**` as a whole component matches any number of path components.
+	///
+	/// The tree is walked depth-first, visiting the entries of each directory sorted by name.
+	/// Directories that were already visited through a different path, such as symbolic link loops, are skipped.
+	///
+	/// Files that are already tracked are not read again, so walking the same directory twice does not insert duplicates.
+	///
+	/// Returns the path and the result of inserting each file, in the order they were visited.
+	/// Failing to read a file or directory does not stop the walk,
+	/// the error is reported as the result for the path of that file or directory.
+	///
+	/// Fails if a glob is invalid or if `root` is not a directory.
+	pub fn insert_dir(
+		&self,
+		root: impl AsRef<Path>,
+		include_globs: &[&str],
+		exclude_globs: &[&str],
+	) -> std::io::Result<InsertDirResults<'_, Data::Slice>> {
+		let root = root.as_ref();
+		let parse = |globs: &[&str]| {
+			globs
+				.iter()
+				.map(|glob| Glob::new(glob))
+				.collect::<std::io::Result<Vec<_>>>()
+		};
+		let filter = DirFilter {
+			include: parse(include_globs)?,
+			exclude: parse(exclude_globs)?,
+		};
+		if !self.file_system.metadata(root)?.is_dir {
+			let message = format!("not a directory: {}", root.display());
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, message));
+		}
+
+		let mut results = Vec::new();
+		self.walk_dir(root, Path::new(""), &filter, &mut BTreeSet::new(), &mut results);
+		Ok(results)
+	}
+
+	/// Recursively insert the files in a directory for [`Self::insert_dir`].
+	fn walk_dir<'a>(
+		&'a self,
+		dir: &Path,
+		relative: &Path,
+		filter: &DirFilter,
+		visited: &mut BTreeSet<PathBuf>,
+		results: &mut InsertDirResults<'a, Data::Slice>,
+	) {
+		let canonical = self
+			.file_system
+			.canonicalize(dir)
+			.unwrap_or_else(|_| remove_cur_dir(dir));
+		if !visited.insert(canonical) {
+			return;
+		}
+
+		let mut entries = match self.file_system.read_dir(dir) {
+			Ok(entries) => entries,
+			Err(e) => return results.push((dir.to_path_buf(), Err(e))),
+		};
+		entries.sort();
+		for path in entries {
+			let relative = match path.file_name() {
+				Some(name) => relative.join(name),
+				None => continue,
+			};
+			if filter.exclude.iter().any(|glob| glob.matches(&relative)) {
+				continue;
+			}
+			match self.file_system.metadata(&path) {
+				Err(e) => results.push((path, Err(e))),
+				Ok(metadata) if metadata.is_dir => self.walk_dir(&path, &relative, filter, visited, results),
+				Ok(_) => {
+					if filter.include.is_empty() || filter.include.iter().any(|glob| glob.matches(&relative)) {
+						let result = self.insert_file_once(path.clone());
+						results.push((path, result));
+					}
+				}
+			}
+		}
+	}
+
+	/// Read a file and insert it into the tracker, unless it is already tracked.
+	///
+	/// If the file is already tracked, the tracked data is returned without reading the file again.
+	/// Otherwise, the file is read in the same way as by [`Self::insert_file`].
+	pub fn insert_file_once(&self, path: impl Into<PathBuf>) -> std::io::Result<&Data::Slice> {
+		let path = path.into();
+		match self.get_file(&path) {
+			Some(data) => Ok(data),
+			None => self.insert_file(path),
+		}
+	}
+
+	/// Add a tracked file to the path index.
+	fn index_path(&self, path: Arc<Path>, data: &Data::Slice, reader: Option<FileReader<Data, Fs>>) {
+		let bytes = Data::as_bytes(data);
+		let key = normalize_path(&path, self.case_insensitive_paths);
+		let file = IndexedFile {
+			path,
+			data: (bytes.as_ptr(), bytes.len()),
+			reader,
+		};
+		self.paths.borrow_mut().insert(key, file);
+	}
+
+	/// Get the data of a tracked file by path.
+	///
+	/// Paths are matched as described for [`Self::set_case_insensitive_paths`].
+	/// If the same file was inserted more than once, the last inserted data is returned.
+	pub fn get_file(&self, path: impl AsRef<Path>) -> Option<&Data::Slice> {
+		self.get_file_entry(path.as_ref()).map(|(data, _)| data)
+	}
+
+	/// Check if a file is tracked.
+	///
+	/// Paths are matched as described for [`Self::set_case_insensitive_paths`].
+	pub fn contains_file(&self, path: impl AsRef<Path>) -> bool {
+		self.get_file_entry(path.as_ref()).is_some()
+	}
+
+	/// Check if a tracked file changed on disk since it was read.
+	///
+	/// The file is considered changed if its size changed,
+	/// or if its modification time changed and its contents changed too.
+	/// The contents are compared with a hash that is recorded when the file is read,
+	/// and they are only read again if the size is the same but the modification time changed.
+	/// A change that keeps both the size and the modification time of the file is not detected.
+	///
+	/// Fails if the file is not tracked, if the data was not read from the file by the tracker,
+	/// or if reading the file fails.
+	pub fn is_stale(&self, path: impl AsRef<Path>) -> std::io::Result<bool> {
+		let key = normalize_path(path.as_ref(), self.case_insensitive_paths);
+		let paths = self.paths.borrow();
+		let file = indexed_file(&paths, &key)?;
+		file.reader()?.stamp.is_stale(&self.file_system, &file.path)
+	}
+
+	/// Read a tracked file again and replace the tracked data.
+	///
+	/// The file is read in the same way as when it was inserted, with the same requested encoding.
+	/// The old data and source are removed from the tracker and returned together with the new data,
+	/// so that data derived from the old data can be invalidated.
+	/// Slices of the old data can no longer be looked up in the tracker.
+	///
+	/// Fails if the file is not tracked, if the data was not read from the file by the tracker,
+	/// if other tracked data still refers to the old data (such as expansions, mapped data or included files),
+	/// or if reading or decoding the file fails.
+	/// The tracker is not modified if reloading fails.
+	pub fn reload(&mut self, path: impl AsRef<Path>) -> std::io::Result<Reloaded<'_, Data>> {
+		let key = normalize_path(path.as_ref(), self.case_insensitive_paths);
+		let file = indexed_file(self.paths.get_mut(), &key)?;
+		let reader = file.reader()?;
+		let (read, requested_encoding) = (reader.read, reader.encoding);
+		let (path, (start, len)) = (file.path.clone(), file.data);
+
+		if self.is_referenced(start, len) {
+			let message = format!("file is still referenced by other tracked data: {}", path.display());
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, message));
+		}
+
+		let (data, encoding, compression, offsets, stamp) = read(&self.file_system, &path, requested_encoding)?;
+
+		// The path index only refers to tracked data, so removing it can not fail.
+		let (old_data, old_entry) = if len == 0 {
+			let (empty, _, _) = Data::decode(Vec::new())?;
+			let (_sentinel, entry) = self.empty_files.remove_raw(start as *const _, 1).unwrap();
+			(empty, entry)
+		} else {
+			self.tracker.remove_raw(start as *const _, len).unwrap()
+		};
+
+		let source = match &old_entry.source {
+			&Source::IncludedFrom { directive, .. } => Source::IncludedFrom {
+				path,
+				encoding,
+				compression,
+				directive,
+			},
+			_ => Source::File {
+				path,
+				encoding,
+				compression,
+			},
+		};
+		let reader = FileReader {
+			read,
+			encoding: requested_encoding,
+			stamp,
+		};
+		let data = self.insert_decoded(source, data, offsets, Some(reader))?;
+		Ok(Reloaded {
+			old_data,
+			old_source: old_entry.source,
+			data,
+		})
+	}
+
+	/// Check if the source of any other tracked data refers to the tracked data given as start pointer and length.
+	fn is_referenced(&self, start: *const u8, len: usize) -> bool {
+		let (_, target) = match self.get_entry_raw(start, len) {
+			Some(entry) => entry,
+			None => return false,
+		};
+		self.tracker
+			.iter()
+			.chain(self.empty_files.iter())
+			.filter(|(_, entry)| !std::ptr::eq(*entry, target))
+			.flat_map(|(_, entry)| entry.source.references())
+			.any(|(start, len)| match self.get_entry_raw(start as *const u8, len) {
+				Some((_, entry)) => std::ptr::eq(entry, target),
+				None => false,
+			})
+	}
+
+	/// Check if a slice is tracked.
+	///
+	/// Empty slices are only tracked if they are the data of an empty file.
+	pub fn is_tracked(&self, data: &Data::Slice) -> bool {
+		self.get_entry(data).is_some()
+	}
+
+	/// Get the whole tracked slice and source for a (partial) slice.
+	pub fn get(&self, data: &Data::Slice) -> Option<(&Data::Slice, &Source<Data::Slice>)> {
+		self.get_entry(data).map(|(data, entry)| (data, &entry.source))
+	}
+
+	/// Get the source for a (partial) slice.
+	pub fn source(&self, data: &Data::Slice) -> Option<&Source<Data::Slice>> {
+		self.get_entry(data).map(|(_, entry)| &entry.source)
+	}
+
+	/// Get the whole tracked slice for a (partial) slice.
+	pub fn whole_slice(&self, data: &Data::Slice) -> Option<&Data::Slice> {
+		self.get_entry(data).map(|(data, _)| data)
+	}
+
+	/// Get all tracked files, including empty files, sorted by normalized path.
+	///
+	/// If the same file was inserted more than once, only the last inserted data is returned.
+	pub fn files(&self) -> impl Iterator<Item = (&Path, &Data::Slice)> {
+		let paths = self.paths.borrow();
+		let files: Vec<_> = paths
+			.values()
+			.filter_map(|file| {
+				let (start, len) = file.data;
+				let (data, entry) = self.get_entry_raw(start, len)?;
+				Some((entry.source.path()?.as_ref(), data))
+			})
+			.collect();
+		files.into_iter()
+	}
+
+	/// Get the whole tracked slice and entry for a (partial) slice.
+	fn get_entry(&self, data: &Data::Slice) -> Option<(&Data::Slice, &FileEntry<Data::Slice>)> {
+		let data = Data::as_bytes(data);
+		self.get_entry_raw(data.as_ptr(), data.len())
+	}
+
+	/// Get the whole tracked slice and entry for a (partial) slice given as start pointer and length.
+	fn get_entry_raw(&self, start: *const u8, len: usize) -> Option<(&Data::Slice, &FileEntry<Data::Slice>)> {
+		if len > 0 {
+			return self.tracker.get_raw(start as *const _, len);
+		}
+
+		// Empty slices are only tracked if they point to the middle of an empty file sentinel.
+		let (sentinel, entry) = self.empty_files.get_raw(start as *const _, 1)?;
+		if start != Data::as_bytes(sentinel)[1..].as_ptr() {
+			return None;
+		}
+		Some((Data::get_subslice(sentinel, 1..1)?, entry))
+	}
+
+	/// Get the data and entry of a tracked file by path.
+	fn get_file_entry(&self, path: &Path) -> Option<(&Data::Slice, &FileEntry<Data::Slice>)> {
+		let key = normalize_path(path, self.case_insensitive_paths);
+		let (start, len) = self.paths.borrow().get(&key)?.data;
+		self.get_entry_raw(start, len)
+	}
+
+	/// Get a tracked subslice given as start pointer and length.
+	fn get_subslice_raw(&self, start: *const <Data::Slice as Slice>::Element, len: usize) -> Option<&Data::Slice> {
+		let (whole, _) = self.get_entry_raw(start as *const u8, len)?;
+		let range = subslice_range(start as *const u8, len, Data::as_bytes(whole));
+		Data::get_subslice(whole, range)
+	}
+
+	/// Get the source location for a slice of data.
+	///
+	/// For data from an included file, this returns the location in the included file and the whole include chain.
+	/// The include directives are resolved in the same way as by [`Self::resolve_source_location`].
+	/// Returns `None` if the data is not tracked or if the includes form a cycle.
+	pub fn get_source_location(&self, data: &Data::Slice) -> Option<SourceLocation<'_, Data::Slice>> {
+		self.get_source_location_in(data, data)
+	}
+
+	/// Get the source location for a possibly empty slice of data inside a tracked slice.
+	///
+	/// Unlike [`Self::get_source_location`], this also works for empty slices in the middle of tracked data.
+	/// The data must be contained in the tracked slice.
+	pub(crate) fn get_source_location_in(
+		&self,
+		tracked: &Data::Slice,
+		data: &Data::Slice,
+	) -> Option<SourceLocation<'_, Data::Slice>> {
+		let location = self.location_in(tracked, data)?;
+		self.with_include_chain(location, tracked)
+	}
+
+	/// Get the source location for a slice of data inside a tracked slice, without the include chain.
+	fn location_in(&self, tracked: &Data::Slice, data: &Data::Slice) -> Option<SourceLocation<'_, Data::Slice>> {
+		let (whole_slice, entry) = self.get_entry(tracked)?;
+		Some(match &entry.source {
+			Source::Unknown => SourceLocation::Unknown,
+			Source::ExpandedFrom(source, len) => self
+				.get_subslice_raw(*source, *len)
+				.map_or(SourceLocation::Unknown, SourceLocation::ExpandedFrom),
+			Source::Expansion {
+				definition,
+				invocation,
+				label,
+			} => {
+				let definition = self.get_subslice_raw(definition.0, definition.1);
+				let invocation = self.get_subslice_raw(invocation.0, invocation.1);
+				match (definition, invocation) {
+					(Some(definition), Some(invocation)) => SourceLocation::Expansion(Expansion {
+						definition,
+						invocation,
+						label: label.as_deref(),
+					}),
+					_ => SourceLocation::Unknown,
+				}
+			}
+			Source::Mapped { fragments } => {
+				let offset = offset_in(Data::as_bytes(data), Data::as_bytes(whole_slice));
+				let index = fragments.partition_point(|&(start, _)| start <= offset);
+				match index.checked_sub(1).map(|index| fragments[index]) {
+					Some((start, (source, len))) if offset < start + len => {
+						let skip = offset - start;
+						let len = Data::as_bytes(data).len().min(len - skip);
+						self.get_subslice_raw(source.wrapping_add(skip), len)
+							.map_or(SourceLocation::Unknown, SourceLocation::MappedFrom)
+					}
+					_ => SourceLocation::Unknown,
+				}
+			}
+			Source::File { path, .. } => self.file_location(path, data, whole_slice, entry),
+			Source::Generated { name } => {
+				let whole_bytes = Data::as_bytes(whole_slice);
+				let data = Data::as_bytes(data);
+				let (line, column) = entry.location(data, whole_bytes, self.line_endings);
+				SourceLocation::Generated(GeneratedLocation {
+					name,
+					line,
+					column,
+					offset: entry.offsets.file_offset(offset_in(data, whole_bytes)),
+				})
+			}
+			Source::IncludedFrom { path, .. } => self.file_location(path, data, whole_slice, entry),
+		})
+	}
+
+	/// Add the include chain of a tracked slice to its source location.
+	///
+	/// Returns `None` if the includes form a cycle.
+	fn with_include_chain<'a>(
+		&'a self,
+		location: SourceLocation<'a, Data::Slice>,
+		tracked: &Data::Slice,
+	) -> Option<SourceLocation<'a, Data::Slice>> {
+		let mut chain: Vec<IncludeFrame<Data::Slice>> = Vec::new();
+		let mut next = self.include_directive(tracked);
+		while let Some(directive) = next {
+			if chain
+				.iter()
+				.any(|frame| frame.directive.start_ptr() == directive.start_ptr())
+			{
+				return None;
+			}
+			let (location, source) = self.resolve_without_includes(directive)?;
+			chain.push(IncludeFrame { directive, location });
+			next = self.include_directive(source);
+		}
+		if chain.is_empty() {
+			Some(location)
+		} else {
+			Some(SourceLocation::IncludedFrom(Box::new(location), chain))
+		}
+	}
+
+	/// Get the include directive of a tracked slice, if it was read from an included file.
+	fn include_directive(&self, data: &Data::Slice) -> Option<&Data::Slice> {
+		let (_, entry) = self.get_entry(data)?;
+		match &entry.source {
+			Source::IncludedFrom {
+				directive: (start, len),
+				..
+			} => self.get_subslice_raw(*start, *len),
+			_ => None,
+		}
+	}
+
+	/// Get the path of a file to show in source locations.
+	///
+	/// See [`Self::add_root`] for how the path is chosen.
+	fn display_path<'a>(&self, path: &'a Path, entry: &'a FileEntry<Data::Slice>) -> &'a Path {
+		let absolute = match &entry.absolute_path {
+			Some(absolute) if !self.roots.is_empty() => absolute,
+			_ => return path,
+		};
+		self.roots
+			.iter()
+			.filter_map(|root| absolute.strip_prefix(root).ok())
+			.min_by_key(|relative| relative.components().count())
+			.unwrap_or(absolute)
+	}
+
+	/// Compute the location of a slice of data in a file.
+	fn file_location<'a>(
+		&'a self,
+		path: &'a Arc<Path>,
+		data: &Data::Slice,
+		whole_slice: &Data::Slice,
+		entry: &'a FileEntry<Data::Slice>,
+	) -> SourceLocation<'a, Data::Slice> {
+		let whole_bytes = Data::as_bytes(whole_slice);
+		let data = Data::as_bytes(data);
+		let offset = entry.offsets.file_offset(offset_in(data, whole_bytes));
+		let display_path = self.display_path(path, entry);
+		if !Data::LINE_LOCATIONS {
+			return SourceLocation::FileOffset(FileOffset {
+				path,
+				display_path,
+				offset,
+				shared_path: path,
+			});
+		}
+
+		let (line, column) = entry.location(data, whole_bytes, self.line_endings);
+		SourceLocation::File(FileLocation {
+			path,
+			display_path,
+			line,
+			column,
+			offset,
+			shared_path: path,
+		})
+	}
+
+	/// Get the source location for a slice of data, following expansions back to the original data.
+	///
+	/// Expansions are followed to their definition, and copied data to the data it was copied from.
+	/// The returned location is never [`SourceLocation::ExpandedFrom`], [`SourceLocation::Expansion`]
+	/// or [`SourceLocation::MappedFrom`].
+	/// Returns `None` if the data is not tracked or if the expansions form a cycle.
+	pub fn resolve_source_location(&self, data: &Data::Slice) -> Option<SourceLocation<'_, Data::Slice>> {
+		self.resolve_location(self.get_source_location(data)?)
+	}
+
+	/// Get the source location of a slice of data in a form that can be serialized.
+	///
+	/// Unlike the location returned by [`Self::get_source_location`],
+	/// expansions and copied data are serialized as the resolved location of the original data.
+	/// Returns `None` if the data is not tracked.
+	#[cfg(feature = "serde")]
+	pub fn serializable_location<'a>(&'a self, data: &'a Data::Slice) -> Option<SerializableLocation<'a, Data, Fs>> {
+		self.get_entry(data)?;
+		Some(SerializableLocation { tracker: self, data })
+	}
+
+	/// Follow expansions in a source location back to the original data.
+	///
+	/// See [`Self::resolve_source_location`].
+	pub(crate) fn resolve_location<'a>(
+		&'a self,
+		location: SourceLocation<'a, Data::Slice>,
+	) -> Option<SourceLocation<'a, Data::Slice>> {
+		let source = match location {
+			SourceLocation::ExpandedFrom(source) | SourceLocation::MappedFrom(source) => source,
+			SourceLocation::Expansion(expansion) => expansion.definition,
+			location => return Some(location),
+		};
+		let (location, data) = self.resolve_without_includes(source)?;
+		self.with_include_chain(location, data)
+	}
+
+	/// Follow expansions from a slice of data back to the original data, without adding the include chain.
+	///
+	/// Returns the location and the slice of original data it is the location of.
+	fn resolve_without_includes<'a>(
+		&'a self,
+		mut data: &'a Data::Slice,
+	) -> Option<(SourceLocation<'a, Data::Slice>, &'a Data::Slice)> {
+		let mut seen = Vec::new();
+		loop {
+			let source = match self.location_in(data, data)? {
+				SourceLocation::ExpandedFrom(source) | SourceLocation::MappedFrom(source) => source,
+				SourceLocation::Expansion(expansion) => expansion.definition,
+				location => return Some((location, data)),
+			};
+			if seen.contains(&source.start_ptr()) {
+				return None;
+			}
+			seen.push(source.start_ptr());
+			data = source;
+		}
+	}
+
+	/// Get the resolved source location of a slice of data, together with the backtrace of expansion call sites.
+	///
+	/// The location is the same as returned by [`Self::resolve_source_location`].
+	/// The backtrace holds a frame for each expansion the data was produced by, from the innermost to the outermost,
+	/// similar to the "in this macro invocation" notes of a compiler.
+	/// Each frame holds the invocation of the expansion and its resolved location,
+	/// and the next frame is the expansion that produced the invocation, if any.
+	///
+	/// Returns `None` if the data is not tracked or if the expansions form a cycle.
+	pub fn resolve_source_location_with_backtrace(
+		&self,
+		data: &Data::Slice,
+	) -> Option<ResolvedLocation<'_, Data::Slice>> {
+		let mut location = None;
+		let mut backtrace: Vec<ExpansionFrame<Data::Slice>> = Vec::new();
+		let mut seen = Vec::new();
+		let mut data = data;
+		// Whether the resolved location of `data` is still needed, for the data itself or for the last frame.
+		let mut resolve = true;
+		loop {
+			let step = self.location_in(data, data)?;
+			let next = match &step {
+				SourceLocation::ExpandedFrom(source) | SourceLocation::MappedFrom(source) => Some((*source, None)),
+				SourceLocation::Expansion(expansion) => Some((expansion.invocation, Some(expansion.label))),
+				_ => None,
+			};
+			if resolve {
+				let resolved = self.resolve_location(self.with_include_chain(step, data)?)?;
+				match backtrace.last_mut() {
+					Some(frame) => frame.location = resolved,
+					None => location = Some(resolved),
+				}
+			}
+
+			// Follow invocations instead of definitions to find the call sites.
+			let (source, label) = match next {
+				Some(next) => next,
+				None => break,
+			};
+			resolve = label.is_some();
+			if let Some(label) = label {
+				backtrace.push(ExpansionFrame {
+					label,
+					invocation: source,
+					location: SourceLocation::Unknown,
+				});
+			}
+			if seen.contains(&source.start_ptr()) {
+				return None;
+			}
+			seen.push(source.start_ptr());
+			data = source;
+		}
+		Some(ResolvedLocation {
+			location: location?,
+			backtrace,
+		})
+	}
+
+	/// Get the source locations of the whole include chain of a slice of data.
+	///
+	/// The first location is the resolved location of the data itself,
+	/// followed by the resolved location of each include directive, up to the outermost file.
+	/// None of the returned locations are [`SourceLocation::ExpandedFrom`] or [`SourceLocation::IncludedFrom`].
+	/// Returns `None` if the data is not tracked or if the expansions or includes form a cycle.
+	pub fn include_chain(&self, data: &Data::Slice) -> Option<Vec<SourceLocation<'_, Data::Slice>>> {
+		Some(match self.resolve_source_location(data)? {
+			SourceLocation::IncludedFrom(location, chain) => std::iter::once(*location)
+				.chain(chain.into_iter().map(|frame| frame.location))
+				.collect(),
+			location => vec![location],
+		})
+	}
+
+	/// Get the line containing the start of a slice of data.
+	///
+	/// Returns the line number and the line itself, without the line terminator.
+	/// Lines are counted in the whole tracked slice, the same way as for [`Self::get_source_location`].
+	///
+	/// Data that reports source locations as byte offset, such as `Vec<u8>`, is split into lines the same way,
+	/// even though [`Self::get_source_location`] does not report line numbers for it.
+	pub fn line_of(&self, data: &Data::Slice) -> Option<(usize, &Data::Slice)> {
+		let (whole_slice, entry) = self.get_entry(data)?;
+		let whole_bytes = Data::as_bytes(whole_slice);
+		let lines = entry.lines(whole_bytes, self.line_endings);
+		let line = lines.line_at(offset_in(Data::as_bytes(data), whole_bytes));
+		Some((line, Data::get_subslice(whole_slice, lines.line_range(line)?)?))
+	}
+
+	/// Get the lines spanned by a slice of data with some context lines before and after it.
+	///
+	/// Returns the line number and the line itself for each line, without the line terminators.
+	/// Lines are counted in the whole tracked slice, the same way as for [`Self::get_source_location`].
+	///
+	/// Data that reports source locations as byte offset, such as `Vec<u8>`, is split into lines the same way,
+	/// even though [`Self::get_source_location`] does not report line numbers for it.
+	pub fn context_lines(&self, data: &Data::Slice, before: usize, after: usize) -> Option<Vec<(usize, &Data::Slice)>> {
+		let (whole_slice, entry) = self.get_entry(data)?;
+		let whole_bytes = Data::as_bytes(whole_slice);
+		let lines = entry.lines(whole_bytes, self.line_endings);
+		let data = Data::as_bytes(data);
+		let offset = offset_in(data, whole_bytes);
+		let first = lines.line_at(offset).saturating_sub(before).max(1);
+		let last = (lines.line_at(offset + data.len().max(1) - 1) + after).min(lines.line_count());
+		(first..=last)
+			.map(|line| Some((line, Data::get_subslice(whole_slice, lines.line_range(line)?)?)))
+			.collect()
+	}
+
+	/// Get a subslice of a tracked file by path, line and column.
+	///
+	/// For data that reports source locations as line and column, this is the reverse of [`Self::get_source_location`].
+	/// Data that reports source locations as byte offset, such as `Vec<u8>`, is split into lines the same way as text,
+	/// see [`Self::line_of`].
+	/// Line and column numbers start at 1, and the column is counted in bytes.
+	/// The column may point just past the end of the line, to refer to the line terminator or the end of the file.
+	///
+	/// Fails if the file is not tracked, if the line or column are out of range,
+	/// or if the subslice would not be valid for the data (such as a column inside a multi-byte character).
+	pub fn slice_at(
+		&self,
+		path: impl AsRef<Path>,
+		line: usize,
+		column: usize,
+		len: usize,
+	) -> std::io::Result<&Data::Slice> {
+		use std::io::{Error, ErrorKind};
+
+		let path = path.as_ref();
+		let (data, entry) = self
+			.get_file_entry(path)
+			.ok_or_else(|| Error::new(ErrorKind::NotFound, format!("file not tracked: {}", path.display())))?;
+		let file_len = Data::as_bytes(data).len();
+
+		let lines = entry.lines(Data::as_bytes(data), self.line_endings);
+		let line_range = lines.line_range(line).ok_or_else(|| {
+			let message = format!("line {} out of range, file has {} lines", line, lines.line_count());
+			Error::new(ErrorKind::InvalidInput, message)
+		})?;
+
+		let line_start = entry.column_offset(line_range.start);
+		let line_len = entry.column_offset(line_range.end) - line_start;
+		if column == 0 || column > line_len + 1 {
+			let message = format!("column {} out of range, line {} has {} bytes", column, line, line_len);
+			return Err(Error::new(ErrorKind::InvalidInput, message));
+		}
+
+		let offset = entry.data_offset(line_start + column - 1);
+		if offset + len > file_len {
+			let message = format!(
+				"range of {} bytes at {}:{} extends past the end of the file",
+				len, line, column
+			);
+			return Err(Error::new(ErrorKind::InvalidInput, message));
+		}
+
+		Data::get_subslice(data, offset..offset + len).ok_or_else(|| {
+			let message = format!(
+				"range of {} bytes at {}:{} is not on a character boundary",
+				len, line, column
+			);
+			Error::new(ErrorKind::InvalidInput, message)
+		})
+	}
+}
+
+impl<Fs: FileSystem> FileTracker<String, Fs> {
+	/// Insert a string that was built from fragments of tracked data.
+	///
+	/// Source locations in the string are reported as [`SourceLocation::MappedFrom`] the data they were copied from,
+	/// or as [`SourceLocation::Unknown`] for text that was not copied from tracked data.
+	/// Use [`Self::resolve_source_location`] to get the location in the original file.
+	///
+	/// Consecutive fragments that are adjacent in the same tracked slice are merged.
+	///
+	/// Fails if a fragment is no longer tracked, or if the string is empty.
+	pub fn insert_mapped(&self, builder: SourceMapBuilder) -> std::io::Result<&str> {
+		use std::io::{Error, ErrorKind};
+
+		let (data, fragments) = builder.into_parts();
+		let mut merged: Vec<(usize, (*const u8, usize))> = Vec::with_capacity(fragments.len());
+		let mut last_whole = std::ptr::null();
+		for (offset, (start, len)) in fragments {
+			let (whole, _) = self
+				.get_entry_raw(start, len)
+				.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "source map fragment is not tracked"))?;
+			// Separately tracked slices can be adjacent in memory, so only merge fragments of the same slice.
+			match merged.last_mut() {
+				Some((last_offset, (last_start, last_len)))
+					if whole.as_ptr() == last_whole
+						&& *last_offset + *last_len == offset
+						&& last_start.wrapping_add(*last_len) == start =>
+				{
+					*last_len += len;
+				}
+				_ => merged.push((offset, (start, len))),
+			}
+			last_whole = whole.as_ptr();
+		}
+		self.insert(data, Source::Mapped { fragments: merged })
+			.map_err(|()| Error::new(ErrorKind::InvalidInput, "data is empty or already tracked"))
+	}
+
+	/// Read a text file with a known encoding and insert it into the tracker.
+	///
+	/// A byte order mark is removed from the data if it matches the encoding.
+	///
+	/// Fails if reading or decoding the file fails.
+	pub fn insert_file_with_encoding(&self, path: impl Into<PathBuf>, encoding: Encoding) -> std::io::Result<&str> {
+		let read: ReadFile<String, Fs> = |file_system, path, encoding| {
+			let (data, compression, stamp) = read_binary_file(file_system, path, true)?;
+			let (data, encoding, offsets) = encoding::decode(data, encoding, false)?;
+			Ok((data, Some(encoding), compression, offsets, stamp))
+		};
+		self.insert_read(path.into().into(), None, read, Some(encoding))
+	}
+
+	/// Read a text file and insert it into the tracker, replacing invalid data with U+FFFD REPLACEMENT CHARACTER.
+	///
+	/// The file is decoded according to its byte order mark, or as UTF-8 if it does not have one.
+	/// Invalid data is replaced in the same way as [`String::from_utf8_lossy`].
+	/// Use [`Self::replacements`] to find the replaced data.
+	///
+	/// Fails if reading the file fails.
+	pub fn insert_file_lossy(&self, path: impl Into<PathBuf>) -> std::io::Result<&str> {
+		let read: ReadFile<String, Fs> = |file_system, path, _encoding| {
+			let (data, compression, stamp) = read_binary_file(file_system, path, true)?;
+			let (data, encoding, offsets) = encoding::decode(data, None, true)?;
+			Ok((data, Some(encoding), compression, offsets, stamp))
+		};
+		self.insert_read(path.into().into(), None, read, None)
+	}
+
+	/// Export the mapping of a string that was inserted with [`Self::insert_mapped`] as a Source Map v3.
+	///
+	/// The source map is returned as JSON, and can be used by tools that understand source maps.
+	/// It includes the path and contents of each file the string was copied from.
+	/// Paths are shown relative to the root directories, see [`Self::add_root`].
+	/// Fragments that were not copied from a file, such as generated data, are left unmapped.
+	///
+	/// The generated file name is included in the source map if given.
+	/// Lines are split like in JavaScript, and columns are counted in UTF-16 code units.
+	///
+	/// Fails if the data is not tracked, or if it was not inserted with [`Self::insert_mapped`].
+	pub fn export_source_map(&self, data: &str, file: Option<&str>) -> std::io::Result<String> {
+		use std::io::{Error, ErrorKind};
+
+		let (data, entry) = self
+			.get_entry(data)
+			.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "data is not tracked"))?;
+		let fragments = match &entry.source {
+			Source::Mapped { fragments } => fragments,
+			_ => return Err(Error::new(ErrorKind::InvalidInput, "data was not built from fragments")),
+		};
+
+		let lines = LineIndex::new(data.as_bytes(), LineEndings::Unicode);
+		let mut map = SourceMap {
+			file: file.map(String::from),
+			..SourceMap::default()
+		};
+		let mut mappings = vec![Vec::new(); lines.line_count()];
+		let mut original_files: Vec<(&str, LineIndex)> = Vec::new();
+		let mut push_segment = |offset: usize, source: Option<(usize, usize, usize)>| {
+			let (line, column) = source_map::position(data, &lines, offset);
+			mappings[line].push(Segment { column, source });
+		};
+
+		let mut mapped_until = 0;
+		for &(offset, (start, len)) in fragments {
+			let (original, original_entry) = match self.get_entry_raw(start, len) {
+				Some(entry) => entry,
+				None => continue,
+			};
+			let path = match original_entry.source.path() {
+				Some(path) => path,
+				None => continue,
+			};
+			let index = match original_files
+				.iter()
+				.position(|(file, _)| std::ptr::eq(*file, original))
+			{
+				Some(index) => index,
+				None => {
+					let display_path = self.display_path(path, original_entry);
+					map.sources.push(display_path.to_string_lossy().into_owned());
+					map.sources_content.push(Some(original.to_string()));
+					original_files.push((original, LineIndex::new(original.as_bytes(), LineEndings::Unicode)));
+					original_files.len() - 1
+				}
+			};
+			let (original, original_lines) = &original_files[index];
+
+			if mapped_until < offset && lines.location(mapped_until).1 > 1 {
+				push_segment(mapped_until, None);
+			}
+
+			// Add a segment at the start of the fragment, and at the start of every generated line in it.
+			let original_offset = start as usize - original.as_ptr() as usize;
+			let mut position = offset;
+			while position < offset + len {
+				let (line, column) =
+					source_map::position(original, original_lines, original_offset + position - offset);
+				push_segment(position, Some((index, line, column)));
+				match lines.line_range(lines.line_at(position) + 1) {
+					Some(next_line) => position = next_line.start,
+					None => break,
+				}
+			}
+			mapped_until = offset + len;
+		}
+		if mapped_until > 0 && mapped_until < data.len() && lines.location(mapped_until).1 > 1 {
+			push_segment(mapped_until, None);
+		}
+
+		map.mappings = mappings;
+		Ok(map.to_json())
+	}
+
+	/// Insert a generated string with a Source Map v3 that maps it to original files.
+	///
+	/// Source locations in the string are reported as [`SourceLocation::MappedFrom`] the original data,
+	/// in the same way as for [`Self::insert_mapped`].
+	/// Source maps only map the start of each segment of generated data,
+	/// so slices inside a segment are mapped to the same distance from the start of the segment in the original data,
+	/// up to the end of the original line.
+	///
+	/// The original files are taken from the `sourcesContent` of the source map if it is included,
+	/// and read with [`Self::insert_file_once`] otherwise.
+	/// The paths of the original files are resolved relative to `base_dir`, and `sourceRoot` if present.
+	/// If a file with the same path and contents is already tracked, the tracked data is reused.
+	/// Other contents from the source map are inserted as [`Source::Generated`] named after the source path,
+	/// so that they can not replace the tracked data of the real file in lookups by path.
+	///
+	/// Fails with [`std::io::ErrorKind::InvalidData`] if the source map is invalid,
+	/// if the path of an original file is absolute or leads out of `base_dir` with `..` components,
+	/// or if reading an original file fails.
+	pub fn insert_with_source_map(
+		&self,
+		data: String,
+		source_map: &str,
+		base_dir: impl AsRef<Path>,
+	) -> std::io::Result<&str> {
+		use std::io::{Error, ErrorKind};
+
+		let map = SourceMap::parse(source_map)?;
+		let source_root = Path::new(map.source_root.as_deref().unwrap_or(""));
+		let mut original_files = Vec::with_capacity(map.sources.len());
+		for (source, content) in map
+			.sources
+			.iter()
+			.zip(map.sources_content.iter().chain(std::iter::repeat(&None)))
+		{
+			let relative = source_root.join(source);
+			if !is_contained(&relative) {
+				let message = format!("source path leads out of the base directory: {}", relative.display());
+				return Err(Error::new(ErrorKind::InvalidData, message));
+			}
+			let path = base_dir.as_ref().join(&relative);
+			let original = match content {
+				None => self.insert_file_once(path)?,
+				Some(content) => match self.get_file(&path) {
+					Some(tracked) if tracked == content => tracked,
+					_ => {
+						let source = Source::Generated {
+							name: relative.display().to_string(),
+						};
+						self.insert_decoded(source, content.clone(), OffsetMap::identity(), None)?
+					}
+				},
+			};
+			original_files.push((original, LineIndex::new(original.as_bytes(), LineEndings::Unicode)));
+		}
+
+		let invalid_position = || Error::new(ErrorKind::InvalidData, "source map refers to a position out of range");
+		let lines = LineIndex::new(data.as_bytes(), LineEndings::Unicode);
+		let mut fragments = Vec::new();
+		for (line, segments) in map.mappings.iter().enumerate() {
+			let line_range = match lines.line_range(line + 1) {
+				Some(range) => range,
+				None => break,
+			};
+			let mut segments: Vec<_> = segments
+				.iter()
+				.map(|segment| {
+					(
+						source_map::offset(&data, &lines, line, segment.column).unwrap(),
+						segment.source,
+					)
+				})
+				.collect();
+			segments.sort_by_key(|&(offset, _)| offset);
+
+			for (i, &(offset, source)) in segments.iter().enumerate() {
+				let (index, original_line, original_column) = match source {
+					Some(source) => source,
+					None => continue,
+				};
+				let (original, original_lines) = original_files.get(index).ok_or_else(invalid_position)?;
+				let original_offset = source_map::offset(original, original_lines, original_line, original_column)
+					.ok_or_else(invalid_position)?;
+				let original_end = original_lines.line_range(original_line + 1).unwrap().end;
+				let end = segments.get(i + 1).map_or(line_range.end, |&(end, _)| end);
+				let mut len = (end - offset).min(original_end - original_offset);
+				while !original.is_char_boundary(original_offset + len) {
+					len -= 1;
+				}
+				if len > 0 {
+					fragments.push((offset, (original[original_offset..].as_ptr(), len)));
+				}
+			}
+		}
+
+		self.insert(data, Source::Mapped { fragments })
+			.map_err(|()| Error::new(ErrorKind::InvalidInput, "data is empty or already tracked"))
+	}
+
+	/// Get the replacement characters that were inserted for invalid data when the file was decoded.
+	///
+	/// Returns the replacement characters in the whole tracked slice containing `data`, in order.
+	/// Use [`Self::get_source_location`] on them to find the location of the invalid data in the original file.
+	pub fn replacements(&self, data: &str) -> Option<Vec<&str>> {
+		let (whole_slice, entry) = self.get_entry(data)?;
+		let replacements = entry.offsets.replacements();
+		Some(replacements.iter().map(|range| &whole_slice[range.clone()]).collect())
+	}
+}
+
+#[cfg(feature = "mmap")]
+impl FileTracker<memmap2::Mmap> {
+	/// Memory map a file read-only and insert the mapping into the tracker.
+	///
+	/// The file is not read into memory, so this is suitable for large binary files.
+	/// Compressed files are not decompressed.
+	/// Like other binary data, source locations are reported as byte offset in the file,
+	/// see [`SourceLocation::FileOffset`].
+	/// The file is not split into lines unless that is needed, such as for [`Self::slice_at`].
+	///
+	/// Fails if opening or mapping the file fails.
+	///
+	/// # Safety
+	/// The file must not be modified or truncated while it is mapped,
+	/// see [`memmap2::Mmap::map`] for details.
+	/// This also applies to the new mapping when the file is reloaded with [`Self::reload`].
+	pub unsafe fn insert_file_mmap(&self, path: impl Into<PathBuf>) -> std::io::Result<&[u8]> {
+		let read: ReadFile<memmap2::Mmap, StdFileSystem> = |_file_system, path, _encoding| {
+			let file = std::fs::File::open(path)?;
+			// The caller of `insert_file_mmap` guarantees that the file is not modified while it is mapped.
+			let data = unsafe { memmap2::Mmap::map(&file)? };
+			let stamp = FileStamp::new(&file.metadata()?.into(), &data);
+			Ok((data, None, None, OffsetMap::identity(), stamp))
+		};
+		self.insert_read(path.into().into(), None, read, None)
+	}
+}
+
+#[cfg(feature = "tokio")]
+impl<Data: FileData> FileTracker<Data, StdFileSystem> {
+	/// Read a file asynchronously and insert it into the tracker.
+	///
+	/// The file is read with [`tokio::fs`] and then decoded and inserted synchronously,
+	/// in the same way as by [`Self::insert_file`].
+	/// The file can be reloaded with [`Self::reload`], which reads it synchronously.
+	///
+	/// Fails if reading or decoding the file fails.
+	pub async fn insert_file_async(&self, path: impl Into<PathBuf>) -> std::io::Result<&Data::Slice> {
+		let path: Arc<Path> = path.into().into();
+		let contents = read_binary_file_async(&path, Data::DETECT_COMPRESSION).await;
+		self.insert_read_async(path, contents)
+	}
+
+	/// Read multiple files concurrently and insert them into the tracker.
+	///
+	/// The files are read on tasks spawned on the current tokio runtime.
+	/// When all files have been read, they are decoded and inserted in the same way as by [`Self::insert_file`].
+	///
+	/// Returns the result for each file in the order of the input paths.
+	///
+	/// # Panics
+	/// Panics if called outside of a tokio runtime.
+	pub async fn insert_files_async<P: Into<PathBuf>>(
+		&self,
+		paths: impl IntoIterator<Item = P>,
+	) -> Vec<std::io::Result<&Data::Slice>> {
+		let paths: Vec<Arc<Path>> = paths.into_iter().map(|path| path.into().into()).collect();
+
+		let mut tasks = tokio::task::JoinSet::new();
+		for (index, path) in paths.iter().enumerate() {
+			let path = path.clone();
+			tasks.spawn(async move { (index, read_binary_file_async(&path, Data::DETECT_COMPRESSION).await) });
+		}
+
+		let mut contents: Vec<_> = paths.iter().map(|_| None).collect();
+		while let Some(joined) = tasks.join_next().await {
+			let (index, result) = joined.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+			contents[index] = Some(result);
+		}
+
+		paths
+			.into_iter()
+			.zip(contents)
+			.map(|(path, contents)| self.insert_read_async(path, contents.unwrap()))
+			.collect()
+	}
+
+	/// Decode and insert the contents of a file that was read asynchronously.
+	fn insert_read_async(&self, path: Arc<Path>, contents: BinaryReadResult) -> std::io::Result<&Data::Slice> {
+		let (data, compression, stamp) = contents?;
+		let (data, encoding, offsets) = Data::decode(data)?;
+		let reader = FileReader {
+			read: read_file,
+			encoding: None,
+			stamp,
+		};
+		let source = Source::File {
+			path,
+			encoding,
+			compression,
+		};
+		self.insert_decoded(source, data, offsets, Some(reader))
+	}
+}
+
+impl<Data: FileData, Fs: FileSystem> std::fmt::Debug for FileTracker<Data, Fs> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		self.tracker.fmt(f)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use assert2::assert;
+
+	/// Write a file in a temporary directory unique to this test process.
+	fn temp_file(name: &str, data: &[u8]) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!("slice-tracker-test-{}", std::process::id()));
+		let path = dir.join(name);
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(&path, data).unwrap();
+		path
+	}
+
+	fn file_source<T: Slice + ?Sized>(path: &str) -> Source<T> {
+		Source::File {
+			path: Path::new(path).into(),
+			encoding: None,
+			compression: None,
+		}
+	}
+
+	#[test]
+	fn test_compute_location() {
+		let data = b"hello\nworld";
+		let lines = LineIndex::new(data, LineEndings::Lf);
+
+		assert!(compute_location(&data[0..], data, &lines) == (1, 1));
+		assert!(compute_location(&data[1..], data, &lines) == (1, 2));
+		assert!(compute_location(&data[2..], data, &lines) == (1, 3));
+		assert!(compute_location(&data[3..], data, &lines) == (1, 4));
+		assert!(compute_location(&data[4..], data, &lines) == (1, 5));
+		assert!(compute_location(&data[5..], data, &lines) == (1, 6));
+		assert!(compute_location(&data[6..], data, &lines) == (2, 1));
+		assert!(compute_location(&data[7..], data, &lines) == (2, 2));
+
+		let data = b"a\r\na\n";
+		let lines = LineIndex::new(data, LineEndings::Lf);
+		assert!(compute_location(&data[0..], data, &lines) == (1, 1));
+		assert!(compute_location(&data[1..], data, &lines) == (1, 2));
+		assert!(compute_location(&data[2..], data, &lines) == (1, 3));
+		assert!(compute_location(&data[3..], data, &lines) == (2, 1));
+		assert!(compute_location(&data[4..], data, &lines) == (2, 2));
+
+		// With CRLF line endings, the whole line terminator is at the same column.
+		let lines = LineIndex::new(data, LineEndings::LfCrLf);
+		assert!(compute_location(&data[1..], data, &lines) == (1, 2));
+		assert!(compute_location(&data[2..], data, &lines) == (1, 2));
+		assert!(compute_location(&data[3..], data, &lines) == (2, 1));
+	}
+
+	#[test]
+	fn test_line_endings() {
+		let text = "a\rb\r\nc\u{2028}d";
+		let expected = [
+			(LineEndings::Lf, vec![(1, "a\rb\r"), (2, "c\u{2028}d")], "file.txt:1:5"),
+			(
+				LineEndings::LfCrLf,
+				vec![(1, "a\rb"), (2, "c\u{2028}d")],
+				"file.txt:1:4",
+			),
+			(
+				LineEndings::Any,
+				vec![(1, "a"), (2, "b"), (3, "c\u{2028}d")],
+				"file.txt:2:2",
+			),
+			(
+				LineEndings::Unicode,
+				vec![(1, "a"), (2, "b"), (3, "c"), (4, "d")],
+				"file.txt:2:2",
+			),
+		];
+
+		for (line_endings, lines, crlf_location) in expected {
+			let tracker = FileTracker::<String>::with_line_endings(line_endings);
+			assert!(tracker.line_endings() == line_endings);
+			let data = tracker.insert(String::from(text), file_source("file.txt")).unwrap();
+
+			// Line retrieval.
+			assert!(tracker.context_lines(data, 0, 0) == Some(lines.clone()));
+
+			// Source locations.
+			match tracker.get_source_location(&data[4..]) {
+				Some(SourceLocation::File(location)) => assert!(location.to_string() == crlf_location),
+				other => panic!("expected file location, got {:?}", other),
+			}
+
+			// Lookup by line and column.
+			let (line, last) = *lines.last().unwrap();
+			assert!(tracker.slice_at("file.txt", line, 1, last.len()).unwrap() == last);
+			assert!(tracker.slice_at("file.txt", line + 1, 1, 0).is_err());
+		}
+	}
+
+	#[test]
+	fn test_insert_encoded_file() {
+		let tracker = FileTracker::<String>::new();
+
+		let mut data = vec![0xFF, 0xFE];
+		data.extend("é\nwörld".encode_utf16().flat_map(|x| x.to_le_bytes().to_vec()));
+		let path = temp_file("utf16.txt", &data);
+		let text = tracker.insert_file(&path).unwrap();
+		assert!(text == "é\nwörld");
+		match tracker.source(text) {
+			Some(Source::File { encoding, .. }) => assert!(encoding == &Some(Encoding::Utf16Le)),
+			other => panic!("expected file source, got {:?}", other),
+		}
+		match tracker.get_source_location(&text[4..]) {
+			Some(SourceLocation::File(location)) => {
+				assert!((location.line, location.column) == (2, 2));
+				assert!(location.offset == 8);
+			}
+			other => panic!("expected file location, got {:?}", other),
+		}
+
+		let path = temp_file("latin1.txt", b"\xE9t\xE9");
+		assert!(tracker.insert_file(&path).is_err());
+		let text = tracker.insert_file_with_encoding(&path, Encoding::Latin1).unwrap();
+		assert!(text == "été");
+		match tracker.get_source_location(&text[3..]) {
+			Some(SourceLocation::File(location)) => {
+				assert!((location.line, location.column) == (1, 4));
+				assert!(location.offset == 2);
+			}
+			other => panic!("expected file location, got {:?}", other),
+		}
+
+		// Binary data is not decoded.
+		let tracker = FileTracker::<Vec<u8>>::new();
+		let data = tracker.insert_file(&path).unwrap();
+		assert!(data == b"\xE9t\xE9");
+		match tracker.source(data) {
+			Some(Source::File { encoding, .. }) => assert!(encoding == &None),
+			other => panic!("expected file source, got {:?}", other),
+		}
+		match tracker.get_source_location(&data[2..]) {
+			Some(SourceLocation::FileOffset(location)) => assert!(location.offset == 2),
+			other => panic!("expected file offset, got {:?}", other),
+		}
+	}
+
+	#[test]
+	#[cfg(feature = "gzip")]
+	fn test_insert_compressed_file() {
+		use std::io::Write;
+
+		fn gzip(data: &[u8]) -> Vec<u8> {
+			let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+			encoder.write_all(data).unwrap();
+			encoder.finish().unwrap()
+		}
+
+		let mut tracker = FileTracker::<String>::new();
+		let path = temp_file("compressed.log.gz", &gzip(b"hello\nw\xC3\xB6rld"));
+		let text = tracker.insert_file(&path).unwrap();
+		assert!(text == "hello\nwörld");
+		match tracker.source(text) {
+			Some(Source::File {
+				encoding, compression, ..
+			}) => {
+				assert!(encoding == &Some(Encoding::Utf8));
+				assert!(compression == &Some(Compression::Gzip));
+			}
+			other => panic!("expected file source, got {:?}", other),
+		}
+		match tracker.get_source_location(&text[9..]) {
+			Some(SourceLocation::File(location)) => {
+				assert!((location.line, location.column) == (2, 4));
+				assert!(location.offset == 9);
+			}
+			other => panic!("expected file location, got {:?}", other),
+		}
+
+		// Compressed text files are detected by their magic bytes too.
+		let path = temp_file("compressed.txt", &gzip(b"text"));
+		assert!(tracker.insert_file(&path).unwrap() == "text");
+
+		// Binary files are only decompressed if they have a `.gz` extension.
+		let tracker_binary = FileTracker::<Vec<u8>>::new();
+		let path = temp_file("compressed.bin", &gzip(b"binary"));
+		assert!(tracker_binary.insert_file(&path).unwrap() == &gzip(b"binary")[..]);
+		let path = temp_file("compressed.bin.gz", &gzip(b"binary"));
+		assert!(tracker_binary.insert_file(&path).unwrap() == b"binary");
+
+		// Compressed files are reloaded the same way.
+		let path = temp_file("compressed.txt.gz", &gzip(b"old"));
+		tracker.insert_file(&path).unwrap();
+		std::fs::write(&path, gzip(b"new data")).unwrap();
+		assert!(tracker.is_stale(&path).unwrap());
+		let new = tracker.reload(&path).unwrap().data;
+		assert!(new == "new data");
+
+		// A file with a `.gz` extension must be compressed.
+		let path = temp_file("not-compressed.gz", b"hello");
+		assert!(tracker.insert_file(&path).is_err());
+	}
+
+	#[test]
+	fn test_insert_lossy_file() {
+		let tracker = FileTracker::<String>::new();
+		let path = temp_file("lossy.txt", b"ab\xFFc\n\xE2\x82d");
+		assert!(tracker.insert_file(&path).unwrap_err().kind() == std::io::ErrorKind::InvalidData);
+
+		let text = tracker.insert_file_lossy(&path).unwrap();
+		assert!(text == "ab\u{FFFD}c\n\u{FFFD}d");
+
+		let replacements = tracker.replacements(text).unwrap();
+		assert!(replacements.len() == 2);
+		assert!(std::ptr::eq(replacements[0], &text[2..5]));
+		assert!(std::ptr::eq(replacements[1], &text[7..10]));
+
+		// Columns after a replacement are counted in the original file.
+		let locations: Vec<_> = replacements
+			.iter()
+			.chain([&&text[5..6], &&text[10..]])
+			.map(|data| match tracker.get_source_location(data) {
+				Some(SourceLocation::File(location)) => (location.line, location.column, location.offset),
+				other => panic!("expected file location, got {:?}", other),
+			})
+			.collect();
+		assert!(locations == [(1, 3, 2), (2, 1, 5), (1, 4, 3), (2, 3, 7)]);
+
+		// Looking up a slice by line and column uses the same columns.
+		assert!(std::ptr::eq(tracker.slice_at(&path, 1, 4, 1).unwrap(), &text[5..6]));
+		assert!(std::ptr::eq(tracker.slice_at(&path, 2, 3, 1).unwrap(), &text[10..]));
+		assert!(tracker.slice_at(&path, 1, 5, 0).unwrap() == "");
+		assert!(tracker.slice_at(&path, 1, 6, 0).is_err());
+
+		// Empty files are tracked too.
+		let empty = tracker.insert_file_lossy(temp_file("lossy-empty.txt", b"")).unwrap();
+		assert!(tracker.is_tracked(empty));
+		let empty = tracker
+			.insert_file_with_encoding(temp_file("encoded-empty.txt", b""), Encoding::Latin1)
+			.unwrap();
+		assert!(tracker.is_tracked(empty));
+	}
+
+	#[test]
+	fn test_insert_empty_file() {
+		let tracker = FileTracker::<String>::new();
+		let empty_path = temp_file("empty.txt", b"");
+		let bom_path = temp_file("bom.txt", b"\xEF\xBB\xBF");
+		let other_path = temp_file("other.txt", b"other");
+
+		let empty = tracker.insert_file(&empty_path).unwrap();
+		let bom = tracker.insert_file(&bom_path).unwrap();
+		let other = tracker.insert_file(&other_path).unwrap();
+		assert!(empty == "");
+		assert!(bom == "");
+		assert!(!std::ptr::eq(empty, bom));
+
+		assert!(tracker.is_tracked(empty));
+		assert!(!tracker.is_tracked(""));
+		assert!(!tracker.is_tracked(&other[5..]));
+		assert!(std::ptr::eq(tracker.whole_slice(empty).unwrap(), empty));
+		assert!(tracker.line_of(empty) == Some((1, "")));
+		assert!(tracker.context_lines(empty, 1, 1) == Some(vec![(1, "")]));
+
+		match tracker.get_source_location(empty) {
+			Some(SourceLocation::File(location)) => {
+				assert!(location.to_string() == format!("{}:1:1", empty_path.display()))
+			}
+			other => panic!("expected file location, got {:?}", other),
+		}
+		match tracker.get_source_location(bom) {
+			Some(SourceLocation::File(location)) => {
+				assert!((location.line, location.column, location.offset) == (1, 1, 3))
+			}
+			other => panic!("expected file location, got {:?}", other),
+		}
+
+		// Empty files can be found by path.
+		assert!(std::ptr::eq(tracker.slice_at(&empty_path, 1, 1, 0).unwrap(), empty));
+		assert!(tracker.slice_at(&empty_path, 1, 1, 1).is_err());
+		let files: Vec<_> = tracker.files().collect();
+		assert!(files.len() == 3);
+		assert!(files
+			.iter()
+			.any(|&(path, data)| path == empty_path && std::ptr::eq(data, empty)));
+		assert!(files
+			.iter()
+			.any(|&(path, data)| path == bom_path && std::ptr::eq(data, bom)));
+		assert!(files
+			.iter()
+			.any(|&(path, data)| path == other_path && std::ptr::eq(data, other)));
+	}
+
+	#[test]
+	fn test_binary_file_offset() {
+		fn check<Data: FileData<Slice = [u8]>>(tracker: &FileTracker<Data>, data: &[u8], path: &Path) {
+			match tracker.get_source_location(&data[6..]) {
+				Some(SourceLocation::FileOffset(location)) => {
+					assert!(location.path == path);
+					assert!(location.offset == 6);
+				}
+				other => panic!("expected file offset, got {:?}", other),
+			}
+			// Binary data is not split into lines to report a location.
+			let (_, entry) = tracker.get_entry(data).unwrap();
+			assert!(entry.lines.get().is_none());
+		}
+
+		let path = temp_file("offset.bin", b"hello\nworld");
+		let tracker = FileTracker::<Vec<u8>>::new();
+		check(&tracker, tracker.insert_file(&path).unwrap(), &path);
+
+		#[cfg(feature = "mmap")]
+		{
+			let tracker = FileTracker::<memmap2::Mmap>::new();
+			check(&tracker, unsafe { tracker.insert_file_mmap(&path).unwrap() }, &path);
+			let tracker = FileTracker::<memmap2::Mmap>::new();
+			check(&tracker, tracker.insert_file(&path).unwrap(), &path);
+		}
+	}
+
+	#[test]
+	#[cfg(feature = "mmap")]
+	fn test_insert_file_mmap() {
+		let tracker = FileTracker::<memmap2::Mmap>::new();
+		let path = temp_file("mmap.bin", b"hello\nworld");
+		let data = unsafe { tracker.insert_file_mmap(&path).unwrap() };
+		assert!(data == b"hello\nworld");
+		assert!(std::ptr::eq(tracker.slice_at(&path, 2, 1, 5).unwrap(), &data[6..]));
+		match tracker.get_source_location(&data[6..]) {
+			Some(SourceLocation::FileOffset(location)) => assert!(location.offset == 6),
+			other => panic!("expected file offset, got {:?}", other),
+		}
+
+		let path = temp_file("mmap-empty.bin", b"");
+		let empty = unsafe { tracker.insert_file_mmap(&path).unwrap() };
+		assert!(empty.is_empty());
+		assert!(tracker.is_tracked(empty));
+
+		// Reading a file copies it into an anonymous mapping.
+		let data = tracker.insert_file(temp_file("mmap-copy.bin", b"copy")).unwrap();
+		assert!(data == b"copy");
+		let empty = tracker.insert_file(temp_file("mmap-copy-empty.bin", b"")).unwrap();
+		assert!(tracker.is_tracked(empty));
+	}
+
+	#[tokio::test]
+	#[cfg(feature = "tokio")]
+	async fn test_insert_file_async() {
+		let mut tracker = FileTracker::<String>::new();
+		let path = temp_file("async.txt", b"hello\nworld");
+		let data = tracker.insert_file_async(&path).await.unwrap();
+		assert!(data == "hello\nworld");
+		match tracker.get_source_location(&data[6..]) {
+			Some(SourceLocation::File(location)) => assert!((location.line, location.column) == (2, 1)),
+			other => panic!("expected file location, got {:?}", other),
+		}
+
+		let paths = [
+			temp_file("async-a.txt", b"a"),
+			temp_file("async-missing/b.txt", b"b"),
+			temp_file("async-empty.txt", b""),
+			temp_file("async-c.txt", b"c"),
+		];
+		std::fs::remove_file(&paths[1]).unwrap();
+		let results = tracker.insert_files_async(&paths).await;
+		assert!(results.len() == 4);
+		assert!(results[0].as_ref().unwrap() == &"a");
+		assert!(results[1].as_ref().unwrap_err().kind() == std::io::ErrorKind::NotFound);
+		assert!(results[2].as_ref().unwrap().is_empty());
+		assert!(results[3].as_ref().unwrap() == &"c");
+		assert!(tracker.contains_file(&paths[3]));
+
+		// Files read asynchronously can be reloaded synchronously.
+		std::fs::write(&path, b"HELLO").unwrap();
+		let Reloaded {
+			old_data: old,
+			data: new,
+			..
+		} = tracker.reload(&path).unwrap();
+		assert!(&old == "hello\nworld");
+		assert!(new == "HELLO");
+	}
+
+	#[test]
+	fn test_format_location() {
+		let path = Arc::from(Path::new("/project/src/main.rs"));
+		let location = FileLocation {
+			path: &path,
+			display_path: Path::new("src/main.rs"),
+			line: 12,
+			column: 5,
+			offset: 100,
+			shared_path: &path,
+		};
+		assert!(location.to_string() == "src/main.rs:12:5");
+		assert!(
+			format!("{:?}", location)
+				== r#"FileLocation { path: "/project/src/main.rs", display_path: "src/main.rs", line: 12, column: 5, offset: 100 }"#
+		);
+
+		let path = Arc::from(Path::new("foo.bin"));
+		let location = FileOffset {
+			path: &path,
+			display_path: Path::new("foo.bin"),
+			offset: 0x1a3f,
+			shared_path: &path,
+		};
+		assert!(location.to_string() == "foo.bin:0x1a3f");
+		assert!(location.to_owned_location().to_string() == "foo.bin:0x1a3f");
+		assert!(
+			format!("{:?}", location) == r#"FileOffset { path: "foo.bin", display_path: "foo.bin", offset: 6719 }"#
+		);
+
+		let location = GeneratedLocation {
+			name: "command line",
+			line: 1,
+			column: 7,
+			offset: 6,
+		};
+		assert!(location.to_string() == "<command line>:1:7");
+	}
+
+	#[test]
+	fn test_generated_source() {
+		let tracker = FileTracker::<String>::new();
+		let source = Source::Generated {
+			name: String::from("prelude"),
+		};
+		let data = tracker.insert(String::from("let a = 1;\nlet b = 2;"), source).unwrap();
+		match tracker.get_source_location(&data[15..]) {
+			Some(SourceLocation::Generated(location)) => {
+				assert!(location.name == "prelude");
+				assert!((location.line, location.column) == (2, 5));
+				assert!(location.offset == 15);
+				assert!(location.to_string() == "<prelude>:2:5");
+			}
+			other => panic!("expected generated location, got {:?}", other),
+		}
+
+		// Expansions of generated data resolve to the generated data.
+		let expanded = tracker
+			.insert(String::from("b"), Source::ExpandedFrom(data[15..].as_ptr(), 1))
+			.unwrap();
+		match tracker.resolve_source_location(expanded) {
+			Some(SourceLocation::Generated(location)) => assert!(location.to_string() == "<prelude>:2:5"),
+			other => panic!("expected generated location, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn test_owned_location() {
+		fn assert_send_static<T: Send + 'static>(_: &T) {}
+
+		let tracker = FileTracker::<String>::new();
+		let data = tracker
+			.insert(String::from("hello\nworld"), file_source("hello.txt"))
+			.unwrap();
+		let owned = match tracker.get_source_location(&data[6..]) {
+			Some(SourceLocation::File(location)) => location.to_owned_location(),
+			other => panic!("expected file location, got {:?}", other),
+		};
+		assert_send_static(&owned);
+		assert!(owned.to_string() == "hello.txt:2:1");
+
+		// The path is shared with the tracker, not copied.
+		match tracker.source(data) {
+			Some(Source::File { path, .. }) => {
+				assert!(Arc::ptr_eq(path, &owned.path));
+				assert!(Arc::ptr_eq(path, &owned.display_path));
+			}
+			other => panic!("expected file source, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn test_display_path() {
+		fn location(tracker: &FileTracker<String>, data: &str) -> (PathBuf, String) {
+			match tracker.get_source_location(data) {
+				Some(SourceLocation::File(location)) => (location.path.to_path_buf(), location.to_string()),
+				other => panic!("expected file location, got {:?}", other),
+			}
+		}
+
+		let main_path = temp_file("roots/project/src/main.rs", b"fn main() {}\n");
+		let project = main_path.parent().unwrap().parent().unwrap();
+		let outside_path = temp_file("outside.rs", b"outside");
+
+		// Without roots, paths are shown as they were inserted.
+		let tracker = FileTracker::<String>::new();
+		let relative = tracker
+			.insert(String::from("relative"), file_source("relative.rs"))
+			.unwrap();
+		assert!(location(&tracker, relative).1 == "relative.rs:1:1");
+
+		let mut tracker = FileTracker::<String>::new();
+		tracker.add_root(project.parent().unwrap()).unwrap();
+		tracker.add_root(project).unwrap();
+		assert!(tracker.roots().len() == 2);
+		let main = tracker.insert_file(&main_path).unwrap();
+		let outside = tracker.insert_file(&outside_path).unwrap();
+		let relative = tracker
+			.insert(String::from("relative"), file_source("relative.rs"))
+			.unwrap();
+
+		// The longest matching root is used, and the original path is kept.
+		let (path, display) = location(&tracker, &main[3..]);
+		assert!(path == main_path);
+		assert!(display == format!("{}:1:4", Path::new("src").join("main.rs").display()));
+
+		// Files outside the roots are shown with their absolute path.
+		let (_, display) = location(&tracker, outside);
+		assert!(display == format!("{}:1:1", std::path::absolute(&outside_path).unwrap().display()));
+		let (path, display) = location(&tracker, relative);
+		assert!(path == Path::new("relative.rs"));
+		assert!(display == format!("{}:1:1", std::path::absolute("relative.rs").unwrap().display()));
+	}
+
+	#[test]
+	fn test_slice_at() {
+		use std::io::ErrorKind;
+
+		let tracker = FileTracker::<String>::new();
+		let data = tracker
+			.insert(String::from("hello\nwörld\n"), file_source("hello.txt"))
+			.unwrap();
+
+		let world = tracker.slice_at("hello.txt", 2, 1, 6).unwrap();
+		assert!(std::ptr::eq(world, &data[6..12]));
+		assert!(world == "wörld");
+		match tracker.get_source_location(world) {
+			Some(SourceLocation::File(location)) => assert!(location.to_string() == "hello.txt:2:1"),
+			other => panic!("expected file location, got {:?}", other),
+		}
+
+		// The column may point at the line terminator or the end of the file.
+		assert!(tracker.slice_at("hello.txt", 1, 6, 1).unwrap() == "\n");
+		assert!(tracker.slice_at("hello.txt", 3, 1, 0).unwrap() == "");
+
+		assert!(tracker.slice_at("other.txt", 1, 1, 1).unwrap_err().kind() == ErrorKind::NotFound);
+		assert!(tracker.slice_at("hello.txt", 0, 1, 1).unwrap_err().kind() == ErrorKind::InvalidInput);
+		assert!(tracker.slice_at("hello.txt", 4, 1, 1).unwrap_err().kind() == ErrorKind::InvalidInput);
+		assert!(tracker.slice_at("hello.txt", 1, 0, 1).unwrap_err().kind() == ErrorKind::InvalidInput);
+		assert!(tracker.slice_at("hello.txt", 1, 7, 1).unwrap_err().kind() == ErrorKind::InvalidInput);
+		assert!(tracker.slice_at("hello.txt", 3, 1, 1).unwrap_err().kind() == ErrorKind::InvalidInput);
+
+		// Column 3 of line 2 is inside the multi-byte character.
+		assert!(tracker.slice_at("hello.txt", 2, 3, 1).unwrap_err().kind() == ErrorKind::InvalidInput);
+		assert!(tracker.slice_at("hello.txt", 2, 2, 1).unwrap_err().kind() == ErrorKind::InvalidInput);
+		assert!(tracker.slice_at("hello.txt", 2, 2, 2).unwrap() == "ö");
+
+		// Binary data has no character boundaries.
+		let tracker = FileTracker::<Vec<u8>>::new();
+		let data = tracker
+			.insert(Vec::from("hello\nwörld\n"), file_source("hello.txt"))
+			.unwrap();
+		assert!(std::ptr::eq(
+			tracker.slice_at("hello.txt", 2, 3, 1).unwrap(),
+			&data[8..9]
+		));
+	}
+
+	#[test]
+	fn test_line_of() {
+		let tracker = FileTracker::<String>::new();
+		let data = tracker
+			.insert(String::from("one\r\ntwo\nthree\nfour\n"), Source::Unknown)
+			.unwrap();
+
+		assert!(tracker.line_of(&data[0..1]) == Some((1, "one")));
+		assert!(tracker.line_of(&data[3..4]) == Some((1, "one")));
+		assert!(tracker.line_of(&data[4..5]) == Some((1, "one")));
+		assert!(tracker.line_of(&data[5..7]) == Some((2, "two")));
+		assert!(tracker.line_of(&data[9..12]) == Some((3, "three")));
+		assert!(tracker.line_of("one") == None);
+
+		let two = &data[5..8];
+		assert!(tracker.context_lines(two, 0, 0) == Some(vec![(2, "two")]));
+		assert!(tracker.context_lines(two, 1, 1) == Some(vec![(1, "one"), (2, "two"), (3, "three")]));
+		assert!(tracker.context_lines(two, 5, 0) == Some(vec![(1, "one"), (2, "two")]));
+		assert!(tracker.context_lines(two, 0, 5) == Some(vec![(2, "two"), (3, "three"), (4, "four"), (5, "")]));
+		assert!(tracker.context_lines(&data[6..12], 0, 0) == Some(vec![(2, "two"), (3, "three")]));
+
+		let tracker = FileTracker::<Vec<u8>>::new();
+		let data = tracker.insert(Vec::from("one\r\ntwo"), Source::Unknown).unwrap();
+		assert!(tracker.line_of(&data[6..7]) == Some((2, &b"two"[..])));
+		assert!(tracker.context_lines(&data[6..7], 1, 1) == Some(vec![(1, &b"one"[..]), (2, &b"two"[..])]));
+	}
+
+	#[test]
+	fn test_debug_tracker() {
+		let tracker = FileTracker::<String>::new();
+		let data = tracker.insert(String::from("hello"), file_source("hello.txt")).unwrap();
+		tracker
+			.insert(String::from("world"), Source::ExpandedFrom(data.as_ptr(), data.len()))
+			.unwrap();
+		tracker.insert(String::from("!"), Source::Unknown).unwrap();
+
+		let debug = format!("{:?}", tracker);
+		assert!(
+			debug.contains(r#"Entry { len: 5, meta: File { path: "hello.txt", encoding: None, compression: None } }"#)
+		);
+		assert!(debug.contains(r#"Entry { len: 5, meta: ExpandedFrom { len: 5 } }"#));
+		assert!(debug.contains(r#"Entry { len: 1, meta: Unknown }"#));
+	}
+
+	#[test]
+	fn test_resolve_expansion() {
+		let tracker = FileTracker::<String>::new();
+		let original = tracker
+			.insert(String::from("hello\nworld"), file_source("hello.txt"))
+			.unwrap();
+		let world = &original[6..];
+		let expanded = tracker
+			.insert(
+				String::from("world!"),
+				Source::ExpandedFrom(world.as_ptr(), world.len()),
+			)
+			.unwrap();
+
+		match tracker.get_source_location(&expanded[1..]) {
+			Some(SourceLocation::ExpandedFrom(source)) => assert!(std::ptr::eq(source, world)),
+			other => panic!("expected expansion location, got {:?}", other),
+		}
+		match tracker.resolve_source_location(&expanded[1..]) {
+			Some(SourceLocation::File(location)) => assert!(location.to_string() == "hello.txt:2:1"),
+			other => panic!("expected file location, got {:?}", other),
+		}
+
+		// Expansions from untracked data have an unknown source.
+		let untracked = "untracked";
+		let expanded = tracker
+			.insert(
+				String::from("data"),
+				Source::ExpandedFrom(untracked.as_ptr(), untracked.len()),
+			)
+			.unwrap();
+		match tracker.get_source_location(expanded) {
+			Some(SourceLocation::Unknown) => (),
+			other => panic!("expected unknown location, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn test_expansion_backtrace() {
+		fn file_location(location: &SourceLocation<str>) -> String {
+			match location {
+				SourceLocation::File(location) => location.to_string(),
+				other => panic!("expected file location, got {:?}", other),
+			}
+		}
+
+		let tracker = FileTracker::<String>::new();
+		let main = tracker
+			.insert(String::from("fn main() {\n\touter!();\n}"), file_source("main.rs"))
+			.unwrap();
+		let macros = tracker
+			.insert(String::from("inner!(1)\nbad"), file_source("macros.rs"))
+			.unwrap();
+		let outer_call = &main[13..21];
+		let (outer_body, inner_body) = (&macros[..9], &macros[10..]);
+
+		let outer = tracker
+			.insert_expansion(String::from("inner!(1)"), outer_body, outer_call, Some("outer"))
+			.unwrap();
+		let inner = tracker
+			.insert_expansion(String::from("bad"), inner_body, outer, Some("inner"))
+			.unwrap();
+
+		match tracker.get_source_location(inner) {
+			Some(SourceLocation::Expansion(expansion)) => {
+				assert!(std::ptr::eq(expansion.definition, inner_body));
+				assert!(std::ptr::eq(expansion.invocation, outer));
+				assert!(expansion.label == Some("inner"));
+			}
+			other => panic!("expected expansion location, got {:?}", other),
+		}
+
+		let ResolvedLocation { location, backtrace } =
+			tracker.resolve_source_location_with_backtrace(&inner[1..]).unwrap();
+		assert!(file_location(&location) == "macros.rs:2:1");
+		assert!(file_location(&tracker.resolve_source_location(&inner[1..]).unwrap()) == "macros.rs:2:1");
+		assert!(backtrace.len() == 2);
+		assert!(backtrace[0].label == Some("inner"));
+		assert!(std::ptr::eq(backtrace[0].invocation, outer));
+		assert!(file_location(&backtrace[0].location) == "macros.rs:1:1");
+		assert!(backtrace[1].label == Some("outer"));
+		assert!(std::ptr::eq(backtrace[1].invocation, outer_call));
+		assert!(file_location(&backtrace[1].location) == "main.rs:2:2");
+
+		// Data expanded from the output of an expansion has the same backtrace.
+		let copy = tracker
+			.insert(String::from("bad"), Source::ExpandedFrom(inner.as_ptr(), inner.len()))
+			.unwrap();
+		let ResolvedLocation { location, backtrace } = tracker.resolve_source_location_with_backtrace(copy).unwrap();
+		assert!(file_location(&location) == "macros.rs:2:1");
+		assert!(backtrace.len() == 2);
+		assert!(file_location(&backtrace[1].location) == "main.rs:2:2");
+
+		// Data that was not produced by an expansion has an empty backtrace.
+		let ResolvedLocation { location, backtrace } =
+			tracker.resolve_source_location_with_backtrace(outer_call).unwrap();
+		assert!(file_location(&location) == "main.rs:2:2");
+		assert!(backtrace.is_empty());
+
+		// The definition and invocation must be tracked.
+		let error = tracker
+			.insert_expansion(String::from("x"), "untracked", outer_call, None)
+			.unwrap_err();
+		assert!(error.kind() == std::io::ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	fn test_insert_mapped() {
+		let tracker = FileTracker::<String>::new();
+		let header = tracker
+			.insert(String::from("// header\nint x;\n"), file_source("header.h"))
+			.unwrap();
+		let main = tracker
+			.insert(
+				String::from("#include \"header.h\"\nint main();\n"),
+				file_source("main.c"),
+			)
+			.unwrap();
+
+		let mut builder = SourceMapBuilder::new();
+		builder.push(&header[10..]);
+		builder.push_unmapped("// inlined\n");
+		builder.push(&main[20..]);
+		let data = tracker.insert_mapped(builder).unwrap();
+		assert!(data == "int x;\n// inlined\nint main();\n");
+
+		match tracker.get_source_location(&data[4..5]) {
+			Some(SourceLocation::MappedFrom(source)) => assert!(std::ptr::eq(source, &header[14..15])),
+			other => panic!("expected mapped location, got {:?}", other),
+		}
+		match tracker.resolve_source_location(&data[23..]) {
+			Some(SourceLocation::File(location)) => assert!(location.to_string() == "main.c:2:6"),
+			other => panic!("expected file location, got {:?}", other),
+		}
+		match tracker.get_source_location(&data[7..17]) {
+			Some(SourceLocation::Unknown) => (),
+			other => panic!("expected unknown location, got {:?}", other),
+		}
+
+		// Slices that span multiple fragments are mapped by the fragment they start in.
+		match tracker.get_source_location(&data[4..]) {
+			Some(SourceLocation::MappedFrom(source)) => assert!(source == "x;\n"),
+			other => panic!("expected mapped location, got {:?}", other),
+		}
+
+		// Adjacent fragments of the same tracked slice are merged.
+		let mut builder = SourceMapBuilder::new();
+		builder.push(&main[..9]);
+		builder.push(&main[9..19]);
+		builder.push(&header[..2]);
+		let data = tracker.insert_mapped(builder).unwrap();
+		match tracker.source(data) {
+			Some(Source::Mapped { fragments }) => assert!(fragments.len() == 2),
+			other => panic!("expected mapped source, got {:?}", other),
+		}
+
+		// Fragments must be tracked.
+		let mut builder = SourceMapBuilder::new();
+		builder.push("untracked");
+		let error = tracker.insert_mapped(builder).unwrap_err();
+		assert!(error.kind() == std::io::ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	fn test_source_map() {
+		let tracker = FileTracker::<String>::new();
+		let a = tracker
+			.insert(String::from("let a = 1;\n"), file_source("a.js"))
+			.unwrap();
+		let b = tracker
+			.insert(String::from("// é\nlet b = 2;\nlet c = 3;\n"), file_source("b.js"))
+			.unwrap();
+
+		let mut builder = SourceMapBuilder::new();
+		builder.push_unmapped("(");
+		builder.push(&a[..10]);
+		builder.push_unmapped(")\n");
+		builder.push(&b[6..]);
+		let data = tracker.insert_mapped(builder).unwrap();
+		assert!(data == "(let a = 1;)\nlet b = 2;\nlet c = 3;\n");
+
+		let json = tracker.export_source_map(data, Some("out.js")).unwrap();
+		assert!(
+			&json
+				== r#"{"version":3,"file":"out.js","sources":["a.js","b.js"],"sourcesContent":["let a = 1;\n","// é\nlet b = 2;\nlet c = 3;\n"],"names":[],"mappings":"CAAA,U;ACCA;AACA;"}"#
+		);
+
+		// Importing the exported source map maps the data back to the same files.
+		let imported = tracker.insert_with_source_map(data.to_string(), &json, "").unwrap();
+		match tracker.get_source_location(&imported[5..6]) {
+			Some(SourceLocation::MappedFrom(source)) => assert!(std::ptr::eq(source, &a[4..5])),
+			other => panic!("expected mapped location, got {:?}", other),
+		}
+		match tracker.resolve_source_location(&imported[28..]) {
+			Some(SourceLocation::File(location)) => assert!(location.to_string() == "b.js:3:5"),
+			other => panic!("expected file location, got {:?}", other),
+		}
+		match tracker.get_source_location(&imported[..1]) {
+			Some(SourceLocation::Unknown) => (),
+			other => panic!("expected unknown location, got {:?}", other),
+		}
+
+		// Original files without contents in the source map are read from disk.
+		let path = temp_file("source-map/original.js", b"let x;\n");
+		let json = r#"{"version":3,"sourceRoot":"source-map","sources":["original.js"],"mappings":"AAAA,GAAG"}"#;
+		let generated = tracker
+			.insert_with_source_map(String::from("let y;"), json, path.parent().unwrap().parent().unwrap())
+			.unwrap();
+		match tracker.resolve_source_location(&generated[4..]) {
+			Some(SourceLocation::File(location)) => {
+				assert!(location.path == path);
+				assert!((location.line, location.column) == (1, 5));
+			}
+			other => panic!("expected file location, got {:?}", other),
+		}
+
+		// Contents from the source map do not replace tracked files with the same path.
+		let json = r#"{"version":3,"sources":["a.js"],"sourcesContent":["changed"],"mappings":"AAAA"}"#;
+		let generated = tracker
+			.insert_with_source_map(String::from("changed"), json, "")
+			.unwrap();
+		match tracker.resolve_source_location(&generated[1..]) {
+			Some(SourceLocation::Generated(location)) => {
+				assert!(location.name == "a.js");
+				assert!((location.line, location.column) == (1, 2));
+			}
+			other => panic!("expected generated location, got {:?}", other),
+		}
+		assert!(std::ptr::eq(tracker.get_file("a.js").unwrap(), a));
+
+		// Source paths must stay inside the base directory.
+		for json in [
+			r#"{"version":3,"sources":["../a.js"],"sourcesContent":["x"],"mappings":"AAAA"}"#,
+			r#"{"version":3,"sources":["/etc/passwd"],"mappings":"AAAA"}"#,
+			r#"{"version":3,"sourceRoot":"..","sources":["a.js"],"sourcesContent":["x"],"mappings":"AAAA"}"#,
+		] {
+			let error = tracker
+				.insert_with_source_map(String::from("x"), json, "dir")
+				.unwrap_err();
+			assert!(error.kind() == std::io::ErrorKind::InvalidData);
+		}
+		let json = r#"{"version":3,"sources":["sub/../b.js"],"sourcesContent":["x"],"mappings":"AAAA"}"#;
+		assert!(tracker.insert_with_source_map(String::from("x"), json, "dir").is_ok());
+
+		// Only data built from fragments can be exported.
+		assert!(tracker.export_source_map(a, None).is_err());
+		let error = tracker
+			.insert_with_source_map(String::from("x"), r#"{"version":3,"sources":[],"mappings":"AAAA"}"#, "")
+			.unwrap_err();
+		assert!(error.kind() == std::io::ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn test_reload() {
+		use std::time::{Duration, SystemTime};
+
+		fn set_modified(path: &Path, time: SystemTime) {
+			std::fs::File::options()
+				.write(true)
+				.open(path)
+				.unwrap()
+				.set_modified(time)
+				.unwrap();
+		}
+
+		let mut tracker = FileTracker::<String>::new();
+		let path = temp_file("reload.txt", b"hello");
+		let empty_path = temp_file("reload-empty.txt", b"");
+		let latin1_path = temp_file("reload-latin1.txt", b"\xE9t\xE9");
+		tracker.insert_file(&path).unwrap();
+		tracker.insert_file(&empty_path).unwrap();
+		tracker
+			.insert_file_with_encoding(&latin1_path, Encoding::Latin1)
+			.unwrap();
+		tracker
+			.insert(String::from("inserted"), file_source("inserted.txt"))
+			.unwrap();
+		assert!(!tracker.is_stale(&path).unwrap());
+
+		// Touching a file without changing it does not make it stale.
+		let time = SystemTime::now() - Duration::from_secs(60);
+		set_modified(&path, time);
+		assert!(!tracker.is_stale(&path).unwrap());
+
+		// Changing the contents does, even if the size stays the same.
+		std::fs::write(&path, b"HELLO").unwrap();
+		set_modified(&path, time + Duration::from_secs(1));
+		assert!(tracker.is_stale(&path).unwrap());
+
+		let Reloaded {
+			old_data: old,
+			old_source,
+			data: new,
+		} = tracker.reload(&path).unwrap();
+		assert!(&old == "hello");
+		match old_source {
+			Source::File { path: old_path, .. } => assert!(&*old_path == &path),
+			other => panic!("expected file source, got {:?}", other),
+		}
+		assert!(new == "HELLO");
+		assert!(!tracker.is_tracked(&old));
+		assert!(!tracker.is_stale(&path).unwrap());
+		match tracker.get_source_location(tracker.slice_at(&path, 1, 2, 1).unwrap()) {
+			Some(SourceLocation::File(location)) => assert!(location.column == 2),
+			other => panic!("expected file location, got {:?}", other),
+		}
+
+		// Empty files can be reloaded too.
+		std::fs::write(&empty_path, b"not empty").unwrap();
+		assert!(tracker.is_stale(&empty_path).unwrap());
+		let Reloaded {
+			old_data: old,
+			data: new,
+			..
+		} = tracker.reload(&empty_path).unwrap();
+		assert!(old == "");
+		assert!(new == "not empty");
+
+		// Files are reloaded with the encoding they were inserted with.
+		std::fs::write(&latin1_path, b"\xE9t\xE9 \xE9t\xE9").unwrap();
+		let new = tracker.reload(&latin1_path).unwrap().data;
+		assert!(new == "été été");
+
+		// Failing to reload leaves the tracker unchanged.
+		std::fs::write(&path, b"\xFF").unwrap();
+		assert!(tracker.reload(&path).is_err());
+		assert!(tracker.slice_at(&path, 1, 1, 5).unwrap() == "HELLO");
+
+		assert!(tracker.is_stale("unknown.txt").unwrap_err().kind() == std::io::ErrorKind::NotFound);
+		assert!(tracker.is_stale("inserted.txt").unwrap_err().kind() == std::io::ErrorKind::InvalidInput);
+		assert!(tracker.reload("inserted.txt").is_err());
+
+		// Files that other data still refers to can not be reloaded.
+		let data = tracker.get_file(&latin1_path).unwrap();
+		tracker
+			.insert(String::from("été"), Source::ExpandedFrom(data.as_ptr(), 4))
+			.unwrap();
+		let error = tracker.reload(&latin1_path).unwrap_err();
+		assert!(error.kind() == std::io::ErrorKind::InvalidInput);
+		assert!(tracker.get_file(&latin1_path).unwrap() == "été été");
+
+		// The contents are only compared if the modification time changed,
+		// so a change that keeps the size and modification time is not detected.
+		let path = temp_file("reload-old.txt", b"old");
+		set_modified(&path, time);
+		tracker.insert_file(&path).unwrap();
+		assert!(!tracker.is_stale(&path).unwrap());
+		set_modified(&path, time + Duration::from_secs(1));
+		assert!(!tracker.is_stale(&path).unwrap());
+		std::fs::write(&path, b"new").unwrap();
+		set_modified(&path, time);
+		assert!(!tracker.is_stale(&path).unwrap());
+		set_modified(&path, time + Duration::from_secs(1));
+		assert!(tracker.is_stale(&path).unwrap());
+	}
+
+	#[test]
+	fn test_file_system() {
+		use crate::{MemoryFileSystem, OverlayFileSystem};
+
+		let mut file_system = MemoryFileSystem::new();
+		file_system.insert("main.cfg", &b"a = 1\nb = 2\n"[..]);
+		let mut tracker = FileTracker::<String, _>::with_file_system(file_system, LineEndings::default());
+
+		let data = tracker.insert_file("./main.cfg").unwrap();
+		assert!(data == "a = 1\nb = 2\n");
+		assert!(std::ptr::eq(tracker.get_file("main.cfg").unwrap(), data));
+		match tracker.get_source_location(&data[6..]) {
+			Some(SourceLocation::File(location)) => assert!(location.to_string() == "./main.cfg:2:1"),
+			other => panic!("expected file location, got {:?}", other),
+		}
+		assert!(tracker.insert_file("other.cfg").unwrap_err().kind() == std::io::ErrorKind::NotFound);
+
+		// Changes are detected by size and contents, since in-memory files have no modification time.
+		assert!(!tracker.is_stale("main.cfg").unwrap());
+		tracker.file_system_mut().insert("main.cfg", &b"a = 3\nb = 4\n"[..]);
+		assert!(tracker.is_stale("main.cfg").unwrap());
+		let data = tracker.reload("main.cfg").unwrap().data;
+		assert!(data == "a = 3\nb = 4\n");
+
+		// Unsaved buffers can be overlaid on the real file system.
+		let path = temp_file("overlay.cfg", b"saved");
+		let mut unsaved = MemoryFileSystem::new();
+		unsaved.insert(&path, &b"unsaved"[..]);
+		let file_system = OverlayFileSystem::new(unsaved, StdFileSystem);
+		let tracker = FileTracker::<String, _>::with_file_system(file_system, LineEndings::default());
+		assert!(tracker.insert_file(&path).unwrap() == "unsaved");
+		assert!(tracker.contains_file(&path));
+		assert!(tracker.insert_file(temp_file("lower.cfg", b"lower")).unwrap() == "lower");
+	}
+
+	#[test]
+	fn test_insert_dir() {
+		use crate::MemoryFileSystem;
+
+		let mut file_system = MemoryFileSystem::new();
+		file_system.insert("root/b.cfg", &b"b"[..]);
+		file_system.insert("root/a.cfg", &b"a"[..]);
+		file_system.insert("root/invalid.cfg", &b"\xFF"[..]);
+		file_system.insert("root/notes.txt", &b"notes"[..]);
+		file_system.insert("root/sub/c.cfg", &b"c"[..]);
+		file_system.insert("root/target/d.cfg", &b"d"[..]);
+		let tracker = FileTracker::<String, _>::with_file_system(file_system, LineEndings::default());
+
+		let results = tracker.insert_dir("root", &["**/*.cfg"], &["target"]).unwrap();
+		let results: Vec<_> = results
+			.into_iter()
+			.map(|(path, result)| (path, result.map_err(|e| e.kind())))
+			.collect();
+		assert!(
+			results
+				== [
+					(PathBuf::from("root/a.cfg"), Ok("a")),
+					(PathBuf::from("root/b.cfg"), Ok("b")),
+					(PathBuf::from("root/invalid.cfg"), Err(std::io::ErrorKind::InvalidData)),
+					(PathBuf::from("root/sub/c.cfg"), Ok("c")),
+				]
+		);
+		assert!(tracker.contains_file("root/sub/c.cfg"));
+		assert!(!tracker.contains_file("root/notes.txt"));
+
+		// Without include globs, all files are inserted.
+		let results = tracker.insert_dir("root/sub", &[], &[]).unwrap();
+		assert!(results.len() == 1);
+
+		// Files that are already tracked are not inserted again.
+		let c = tracker.get_file("root/sub/c.cfg").unwrap();
+		assert!(std::ptr::eq(*results[0].1.as_ref().unwrap(), c));
+		let results = tracker.insert_dir("root", &["**/*.cfg"], &["target"]).unwrap();
+		assert!(std::ptr::eq(*results[3].1.as_ref().unwrap(), c));
+		assert!(tracker.tracker.iter().count() == 3);
+
+		assert!(tracker.insert_dir("root/a.cfg", &[], &[]).unwrap_err().kind() == std::io::ErrorKind::InvalidInput);
+		assert!(tracker.insert_dir("root", &["[a"], &[]).unwrap_err().kind() == std::io::ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn test_insert_dir_symlink_loop() {
+		let path = temp_file("walk/dir/a.cfg", b"a");
+		let root = path.parent().unwrap().parent().unwrap();
+		let link = root.join("dir").join("loop");
+		if !link.exists() {
+			std::os::unix::fs::symlink(root, &link).unwrap();
+		}
+
+		let tracker = FileTracker::<String>::new();
+		let results = tracker.insert_dir(root, &[], &[]).unwrap();
+		assert!(results.len() == 1);
+		assert!(&results[0].0 == &path);
+		assert!(results[0].1.as_ref().unwrap() == &"a");
+	}
+
+	#[test]
+	fn test_path_index() {
+		let mut tracker = FileTracker::<String>::new();
+		let path = temp_file("Index.cfg", b"index");
+		let dir = path.parent().unwrap();
+		let dotted = dir.join(".").join("Index.cfg");
+
+		assert!(!tracker.contains_file(&path));
+		assert!(tracker.get_file(&path).is_none());
+		let data = tracker.insert_file_once(&dotted).unwrap();
+		assert!(tracker.contains_file(&path));
+		assert!(std::ptr::eq(tracker.get_file(&path).unwrap(), data));
+		assert!(std::ptr::eq(tracker.insert_file_once(&path).unwrap(), data));
+
+		// The source keeps the path as it was inserted.
+		match tracker.source(data) {
+			Some(Source::File { path, .. }) => assert!(path.as_ref() == dotted),
+			other => panic!("expected file source, got {:?}", other),
+		}
+
+		// Paths of data that was not read from disk are normalized too.
+		tracker
+			.insert(String::from("virtual"), file_source("./virtual.cfg"))
+			.unwrap();
+		assert!(tracker.get_file("virtual.cfg").unwrap() == "virtual");
+
+		assert!(tracker.get_file("VIRTUAL.cfg").is_none());
+		assert!(!tracker.case_insensitive_paths());
+		tracker.set_case_insensitive_paths(true);
+		assert!(tracker.case_insensitive_paths());
+		assert!(tracker.contains_file(dir.join("INDEX.CFG")));
+		assert!(tracker.get_file("VIRTUAL.cfg").unwrap() == "virtual");
+		let data = tracker.get_file(&path).unwrap();
+		assert!(std::ptr::eq(
+			tracker.insert_file_once(dir.join("index.CFG")).unwrap(),
+			data
+		));
+
+		// Parent directory components are resolved lexically.
+		assert!(tracker.get_file(dir.join("sub/../Index.cfg")).is_some());
+		assert!(tracker.get_file("a/b/../../virtual.cfg").unwrap() == "virtual");
+		assert!(tracker.get_file("../virtual.cfg").is_none());
+
+		// Lookups do not depend on the file system, so deleted files are still found
+		// and checking them for changes reports the real error.
+		std::fs::remove_file(&path).unwrap();
+		assert!(tracker.contains_file(dir.join(".").join("Index.cfg")));
+		assert!(tracker.is_stale(&path).unwrap_err().kind() == std::io::ErrorKind::NotFound);
+		assert!(tracker.reload(&path).unwrap_err().kind() == std::io::ErrorKind::NotFound);
+	}
+
+	#[test]
+	fn test_include_chain() {
+		let tracker = FileTracker::<String>::new();
+		let main_path = temp_file("main.cfg", b"a = 1\ninclude \"other.cfg\"\n");
+		let other_path = temp_file("other.cfg", b"b = 2\ninclude \"last.cfg\"\n");
+		let last_path = temp_file("last.cfg", b"c = 3\n");
+
+		let main = tracker.insert_file(&main_path).unwrap();
+		let other = tracker.insert_included_file(&other_path, &main[6..25]).unwrap();
+		let last = tracker.insert_included_file(&last_path, &other[6..24]).unwrap();
+		assert!(tracker.insert_included_file(&last_path, "untracked").is_err());
+
+		match tracker.get_source_location(&last[4..]) {
+			Some(SourceLocation::IncludedFrom(location, chain)) => {
+				match *location {
+					SourceLocation::File(location) => {
+						assert!(location.to_string() == format!("{}:1:5", last_path.display()))
+					}
+					other => panic!("expected file location, got {:?}", other),
+				}
+				assert!(chain.len() == 2);
+				assert!(std::ptr::eq(chain[0].directive, &other[6..24]));
+				assert!(std::ptr::eq(chain[1].directive, &main[6..25]));
+				match &chain[1].location {
+					SourceLocation::File(location) => {
+						assert!(location.to_string() == format!("{}:2:1", main_path.display()))
+					}
+					other => panic!("expected file location, got {:?}", other),
+				}
+			}
+			other => panic!("expected include location, got {:?}", other),
+		}
+
+		let chain: Vec<_> = tracker
+			.include_chain(&last[4..])
+			.unwrap()
+			.into_iter()
+			.map(|location| match location {
+				SourceLocation::File(location) => location.to_string(),
+				other => panic!("expected file location, got {:?}", other),
+			})
+			.collect();
+		assert!(
+			chain
+				== [
+					format!("{}:1:5", last_path.display()),
+					format!("{}:2:1", other_path.display()),
+					format!("{}:2:1", main_path.display()),
+				]
+		);
+
+		// Data that was not included has a chain of one location.
+		assert!(tracker.include_chain(main).unwrap().len() == 1);
+
+		// Included files can be found by path.
+		assert!(std::ptr::eq(
+			tracker.slice_at(&other_path, 1, 1, 5).unwrap(),
+			&other[..5]
+		));
+		assert!(tracker.files().count() == 3);
+	}
+
+	#[test]
+	#[cfg(feature = "serde")]
+	fn test_serde_round_trip() {
+		let path = Arc::from(Path::new("src/main.rs"));
+		let location = FileLocation {
+			path: &path,
+			display_path: Path::new("main.rs"),
+			line: 12,
+			column: 5,
+			offset: 100,
+			shared_path: &path,
+		};
+		let json = serde_json::to_string(&location).unwrap();
+		assert!(&json == r#"{"path":"src/main.rs","display_path":"main.rs","line":12,"column":5,"offset":100}"#);
+		let owned: OwnedFileLocation = serde_json::from_str(&json).unwrap();
+		assert!(&owned == &OwnedFileLocation::from(location));
+		assert!(serde_json::to_string(&owned).unwrap() == json);
+
+		let source = file_source::<str>("src/main.rs");
+		let json = serde_json::to_string(&source).unwrap();
+		assert!(&json == r#"{"File":{"path":"src/main.rs","encoding":null,"compression":null}}"#);
+		assert!(serde_json::from_str::<Source<str>>(&json).unwrap() == source);
+		let json = r#"{"File":{"path":"src/main.rs","encoding":null}}"#;
+		assert!(serde_json::from_str::<Source<str>>(json).unwrap() == file_source("src/main.rs"));
+
+		let source = Source::<str>::Generated {
+			name: String::from("prelude"),
+		};
+		let json = serde_json::to_string(&source).unwrap();
+		assert!(&json == r#"{"Generated":{"name":"prelude"}}"#);
+		assert!(serde_json::from_str::<Source<str>>(&json).unwrap() == source);
+
+		let source = Source::<[u8]>::Unknown;
+		let json = serde_json::to_string(&source).unwrap();
+		assert!(&json == r#""Unknown""#);
+		assert!(serde_json::from_str::<Source<[u8]>>(&json).unwrap() == source);
+	}
+
+	#[test]
+	#[cfg(feature = "serde")]
+	fn test_serde_file_offset() {
+		let tracker = FileTracker::<Vec<u8>>::new();
+		let data = tracker
+			.insert(Vec::from(&b"\x00\x01\x02\x03"[..]), file_source("foo.bin"))
+			.unwrap();
+		let location = match tracker.get_source_location(&data[2..]) {
+			Some(SourceLocation::FileOffset(location)) => location,
+			other => panic!("expected file offset, got {:?}", other),
+		};
+		let json = serde_json::to_string(&location).unwrap();
+		assert!(&json == r#"{"path":"foo.bin","display_path":"foo.bin","offset":2}"#);
+		let owned: OwnedFileOffset = serde_json::from_str(&json).unwrap();
+		assert!(&owned == &OwnedFileOffset::from(location));
+		assert!(serde_json::to_string(&owned).unwrap() == json);
+	}
+
+	#[test]
+	#[cfg(feature = "serde")]
+	fn test_serialize_include_chain() {
+		let tracker = FileTracker::<String>::new();
+		let main = tracker
+			.insert(String::from("include x"), file_source("main.cfg"))
+			.unwrap();
+		let included = tracker
+			.insert(
+				String::from("x = 1"),
+				Source::IncludedFrom {
+					path: Arc::from(Path::new("x.cfg")),
+					encoding: None,
+					compression: None,
+					directive: (main.as_ptr(), main.len()),
+				},
+			)
+			.unwrap();
+		let json = serde_json::to_string(&tracker.serializable_location(&included[4..]).unwrap()).unwrap();
+		assert!(
+			&json
+				== concat!(
+					r#"{"IncludedFrom":[{"File":{"path":"x.cfg","display_path":"x.cfg","line":1,"column":5,"offset":4}},"#,
+					r#"[{"location":{"File":{"path":"main.cfg","display_path":"main.cfg","line":1,"column":1,"offset":0}}}]]}"#,
+				)
+		);
+	}
+
+	#[test]
+	#[cfg(feature = "serde")]
+	fn test_serialize_expansion() {
+		let tracker = FileTracker::<String>::new();
+		let original = tracker
+			.insert(String::from("hello\nworld"), file_source("hello.txt"))
+			.unwrap();
+		let source = Source::<str>::ExpandedFrom(original[6..].as_ptr(), 5);
+		assert!(serde_json::to_string(&source).is_err());
+
+		let expanded = tracker.insert(String::from("world!"), source).unwrap();
+		let location = tracker.get_source_location(expanded).unwrap();
+		assert!(serde_json::to_string(&location).is_err());
+
+		let location = tracker.serializable_location(expanded).unwrap();
+		let json = serde_json::to_string(&location).unwrap();
+		assert!(&json == r#"{"File":{"path":"hello.txt","display_path":"hello.txt","line":2,"column":1,"offset":6}}"#);
+		assert!(json == serde_json::to_string(&tracker.resolve_source_location(expanded).unwrap()).unwrap());
+
+		// Data that is not tracked has no location at all.
+		assert!(tracker.serializable_location("world!").is_none());
 	}
 }
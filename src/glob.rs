@@ -0,0 +1,232 @@
+// Copyright (c) 2018, Maarten de Vries <maarten@de-vri.es>
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//   list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//   this list of conditions and the following disclaimer in the documentation
+//   and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::path::{Component, Path};
+
+/// Glob pattern for matching relative paths.
+///
+/// Patterns are matched against the whole path, with `/` as separator:
+///   * `*` matches any sequence of characters within a path component,
+///   * `?` matches any single character within a path component,
+///   * `[abc]`, `[a-z]` and `[!abc]` match a single character from (or not from) a set,
+///   * `**` as a whole component matches any number of path components, including none.
+#[derive(Clone, Debug)]
+pub struct Glob {
+	/// The pattern for each path component.
+	components: Vec<Vec<Token>>,
+}
+
+/// Part of a glob pattern for a single path component.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+	/// A literal character.
+	Literal(char),
+
+	/// Any single character.
+	Any,
+
+	/// Any sequence of characters.
+	Star,
+
+	/// Any number of path components.
+	Recursive,
+
+	/// A character from a set of ranges, or not from the set if negated.
+	Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+impl Glob {
+	/// Parse a glob pattern.
+	///
+	/// Fails with [`std::io::ErrorKind::InvalidInput`] if a character class is not closed.
+	pub fn new(pattern: &str) -> std::io::Result<Self> {
+		let components = pattern
+			.split('/')
+			.filter(|component| !component.is_empty() && *component != ".")
+			.map(parse_component)
+			.collect::<Option<_>>()
+			.ok_or_else(|| {
+				let message = format!("unclosed character class in glob pattern: {}", pattern);
+				std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+			})?;
+		Ok(Self { components })
+	}
+
+	/// Check if a relative path matches the pattern.
+	pub fn matches(&self, path: &Path) -> bool {
+		let names: Vec<_> = path
+			.components()
+			.filter_map(|component| match component {
+				Component::Normal(name) => Some(name.to_string_lossy().chars().collect::<Vec<_>>()),
+				_ => None,
+			})
+			.collect();
+		match_components(&self.components, &names)
+	}
+}
+
+/// Parse the pattern for a single path component.
+///
+/// Returns `None` if a character class is not closed.
+fn parse_component(pattern: &str) -> Option<Vec<Token>> {
+	if pattern == "**" {
+		return Some(vec![Token::Recursive]);
+	}
+
+	let mut tokens = Vec::new();
+	let mut chars = pattern.chars();
+	while let Some(c) = chars.next() {
+		tokens.push(match c {
+			'?' => Token::Any,
+			'*' => Token::Star,
+			'[' => {
+				let mut class: Vec<char> = Vec::new();
+				loop {
+					match chars.next()? {
+						']' if !class.is_empty() && class != ['!'] => break,
+						c => class.push(c),
+					}
+				}
+				let negated = class[0] == '!';
+				let class = if negated { &class[1..] } else { &class[..] };
+				let mut ranges = Vec::new();
+				let mut i = 0;
+				while i < class.len() {
+					if i + 2 < class.len() && class[i + 1] == '-' {
+						ranges.push((class[i], class[i + 2]));
+						i += 3;
+					} else {
+						ranges.push((class[i], class[i]));
+						i += 1;
+					}
+				}
+				Token::Class { negated, ranges }
+			}
+			c => Token::Literal(c),
+		});
+	}
+	Some(tokens)
+}
+
+/// Check if a list of path components matches a list of component patterns.
+fn match_components(patterns: &[Vec<Token>], names: &[Vec<char>]) -> bool {
+	match_wildcard(
+		patterns,
+		names,
+		|pattern| pattern == &[Token::Recursive],
+		|pattern, name| match_component(pattern, name),
+	)
+}
+
+/// Check if a single path component matches a component pattern.
+fn match_component(tokens: &[Token], name: &[char]) -> bool {
+	match_wildcard(
+		tokens,
+		name,
+		|token| token == &Token::Star,
+		|token, &c| match_char(token, c),
+	)
+}
+
+/// Match a sequence of items against a pattern with wildcards that match any number of items.
+///
+/// This uses a greedy match that only backtracks to the last wildcard,
+/// so it takes at most `O(patterns.len() * items.len())` steps instead of exponential time.
+fn match_wildcard<P, I>(
+	patterns: &[P],
+	items: &[I],
+	is_wildcard: impl Fn(&P) -> bool,
+	matches: impl Fn(&P, &I) -> bool,
+) -> bool {
+	let mut p = 0;
+	let mut i = 0;
+	// The pattern index after the last wildcard, and the item index it was tried at.
+	let mut backtrack = None;
+	while i < items.len() {
+		if p < patterns.len() && is_wildcard(&patterns[p]) {
+			p += 1;
+			backtrack = Some((p, i));
+		} else if p < patterns.len() && matches(&patterns[p], &items[i]) {
+			p += 1;
+			i += 1;
+		} else if let Some((wildcard_p, wildcard_i)) = backtrack {
+			// Let the last wildcard match one more item and try again from there.
+			p = wildcard_p;
+			i = wildcard_i + 1;
+			backtrack = Some((wildcard_p, i));
+		} else {
+			return false;
+		}
+	}
+	patterns[p..].iter().all(is_wildcard)
+}
+
+/// Check if a single character matches a token.
+fn match_char(token: &Token, c: char) -> bool {
+	match token {
+		Token::Literal(literal) => *literal == c,
+		Token::Any => true,
+		Token::Class { negated, ranges } => ranges.iter().any(|&(start, end)| start <= c && c <= end) != *negated,
+		Token::Star | Token::Recursive => false,
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use assert2::assert;
+
+	fn matches(pattern: &str, path: &str) -> bool {
+		Glob::new(pattern).unwrap().matches(Path::new(path))
+	}
+
+	#[test]
+	fn test_glob() {
+		assert!(matches("*.cfg", "a.cfg"));
+		assert!(!matches("*.cfg", "dir/a.cfg"));
+		assert!(!matches("*.cfg", "a.cfg.bak"));
+		assert!(matches("**/*.cfg", "a.cfg"));
+		assert!(matches("**/*.cfg", "dir/sub/a.cfg"));
+		assert!(matches("dir/**", "dir/sub/a.cfg"));
+		assert!(!matches("dir/**", "other/a.cfg"));
+		assert!(matches("dir/**/a.cfg", "dir/a.cfg"));
+		assert!(matches("./a?.cfg", "ab.cfg"));
+		assert!(!matches("a?.cfg", "a.cfg"));
+		assert!(matches("[a-c]x.cfg", "bx.cfg"));
+		assert!(!matches("[!a-c]x.cfg", "bx.cfg"));
+		assert!(matches("[]]", "]"));
+		assert!(Glob::new("[a-c").is_err());
+		assert!(matches("**/a/**/b/*.cfg", "x/a/y/a/z/b/c.cfg"));
+		assert!(!matches("**/a/**/b/*.cfg", "x/a/y/b/z/c.cfg"));
+		assert!(matches("*a*b*c", "xaybzc"));
+		assert!(!matches("*a*b*c", "xaybzcd"));
+	}
+
+	#[test]
+	fn test_glob_no_exponential_backtracking() {
+		let name = "a".repeat(100);
+		assert!(!matches(&format!("{}b", "*a".repeat(20)), &name));
+		let path = vec!["a"; 100].join("/");
+		assert!(!matches(&format!("{}b", "**/a/".repeat(20)), &path));
+	}
+}
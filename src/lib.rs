@@ -21,15 +21,51 @@
 // OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+mod compression;
+mod encoding;
+mod file_stamp;
+mod file_system;
 mod file_tracker;
+mod glob;
+mod line_index;
 mod slice;
 mod slice_tracker;
+mod source_map;
 mod stable_borrow;
+mod token_tracker;
 
+pub use self::compression::Compression;
+pub use self::encoding::Encoding;
+pub use self::file_system::FileHandle;
+pub use self::file_system::FileMetadata;
+pub use self::file_system::FileSystem;
+pub use self::file_system::MemoryFileSystem;
+pub use self::file_system::OverlayFile;
+pub use self::file_system::OverlayFileSystem;
+pub use self::file_system::StdFileSystem;
+pub use self::file_tracker::Expansion;
+pub use self::file_tracker::ExpansionFrame;
+pub use self::file_tracker::FileData;
+pub use self::file_tracker::FileLocation;
+pub use self::file_tracker::FileOffset;
 pub use self::file_tracker::FileTracker;
+pub use self::file_tracker::GeneratedLocation;
+pub use self::file_tracker::IncludeFrame;
+pub use self::file_tracker::OwnedFileLocation;
+pub use self::file_tracker::OwnedFileOffset;
+pub use self::file_tracker::Reloaded;
+pub use self::file_tracker::ResolvedLocation;
+#[cfg(feature = "serde")]
+pub use self::file_tracker::SerializableLocation;
 pub use self::file_tracker::Source;
 pub use self::file_tracker::SourceLocation;
+pub use self::line_index::LineEndings;
 pub use self::slice::BorrowSlice;
 pub use self::slice::Slice;
 pub use self::slice_tracker::SliceTracker;
+pub use self::source_map::SourceMapBuilder;
 pub use self::stable_borrow::StableBorrow;
+pub use self::token_tracker::TokenTracker;
+
+#[cfg(feature = "mmap")]
+pub use memmap2;
@@ -0,0 +1,246 @@
+// Copyright (c) 2018, Maarten de Vries <maarten@de-vri.es>
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//   list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//   this list of conditions and the following disclaimer in the documentation
+//   and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::ops::Range;
+
+/// The line terminators that separate lines.
+///
+/// The same policy is used for computing source locations,
+/// for looking up slices by line and column and for retrieving lines.
+/// Columns inside a line terminator are reported as the column of the first byte of the terminator.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum LineEndings {
+	/// Only `"\n"` terminates a line.
+	Lf,
+
+	/// Both `"\n"` and `"\r\n"` terminate a line.
+	#[default]
+	LfCrLf,
+
+	/// `"\n"`, `"\r\n"` and a lone `"\r"` all terminate a line.
+	Any,
+
+	/// Like [`LineEndings::Any`], but U+2028 LINE SEPARATOR and U+2029 PARAGRAPH SEPARATOR also terminate a line.
+	///
+	/// For binary data, the UTF-8 encoding of these characters terminates a line.
+	Unicode,
+}
+
+impl LineEndings {
+	/// Find the first line terminator in `data` at or after `start`.
+	///
+	/// Returns the byte range of the line terminator.
+	fn find(self, data: &[u8], start: usize) -> Option<Range<usize>> {
+		let haystack = &data[start..];
+		match self {
+			Self::Lf => {
+				let i = start + memchr::memchr(b'\n', haystack)?;
+				Some(i..i + 1)
+			}
+			Self::LfCrLf => {
+				let i = start + memchr::memchr(b'\n', haystack)?;
+				if i > start && data[i - 1] == b'\r' {
+					Some(i - 1..i + 1)
+				} else {
+					Some(i..i + 1)
+				}
+			}
+			Self::Any => {
+				let i = start + memchr::memchr2(b'\n', b'\r', haystack)?;
+				Some(i..i + cr_lf_len(data, i))
+			}
+			Self::Unicode => {
+				for i in memchr::memchr3_iter(b'\n', b'\r', 0xE2, haystack) {
+					let i = start + i;
+					match data[i] {
+						0xE2 => match data.get(i + 1..i + 3) {
+							Some([0x80, 0xA8]) | Some([0x80, 0xA9]) => return Some(i..i + 3),
+							_ => continue,
+						},
+						_ => return Some(i..i + cr_lf_len(data, i)),
+					}
+				}
+				None
+			}
+		}
+	}
+}
+
+/// Get the length of the line terminator at `data[i]`, which must be `'\n'` or `'\r'`.
+fn cr_lf_len(data: &[u8], i: usize) -> usize {
+	if data[i] == b'\r' && data.get(i + 1) == Some(&b'\n') {
+		2
+	} else {
+		1
+	}
+}
+
+/// Index of the lines in a piece of data.
+pub(crate) struct LineIndex {
+	/// The byte range of each line, excluding the line terminator.
+	lines: Vec<Range<usize>>,
+}
+
+impl LineIndex {
+	/// Create a line index for a piece of data.
+	pub fn new(data: &[u8], line_endings: LineEndings) -> Self {
+		let mut lines = Vec::new();
+		let mut start = 0;
+		while let Some(terminator) = line_endings.find(data, start) {
+			lines.push(start..terminator.start);
+			start = terminator.end;
+		}
+		lines.push(start..data.len());
+		Self { lines }
+	}
+
+	/// Get the number of lines in the data.
+	///
+	/// Data that ends with a line break has an empty line at the end.
+	pub fn line_count(&self) -> usize {
+		self.lines.len()
+	}
+
+	/// Get the byte range of a line, excluding the line terminator.
+	///
+	/// Line numbers start at 1.
+	pub fn line_range(&self, line: usize) -> Option<Range<usize>> {
+		self.lines.get(line.checked_sub(1)?).cloned()
+	}
+
+	/// Get the number of the line containing a byte offset.
+	///
+	/// Offsets in a line terminator belong to the line they terminate.
+	pub fn line_at(&self, offset: usize) -> usize {
+		self.lines.partition_point(|line| line.start <= offset)
+	}
+
+	/// Get the line and column of a byte offset.
+	///
+	/// Line and column numbers start at 1, and the column is counted in bytes.
+	pub fn location(&self, offset: usize) -> (usize, usize) {
+		let line = self.line_at(offset);
+		let range = &self.lines[line - 1];
+		(line, offset.min(range.end) - range.start + 1)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use assert2::assert;
+
+	fn lines(data: &[u8], line_endings: LineEndings) -> Vec<&[u8]> {
+		let index = LineIndex::new(data, line_endings);
+		(1..=index.line_count())
+			.map(|line| &data[index.line_range(line).unwrap()])
+			.collect()
+	}
+
+	#[test]
+	fn test_line_range() {
+		let index = LineIndex::new(b"hello\nworld\n", LineEndings::default());
+		assert!(index.line_count() == 3);
+		assert!(index.line_range(0) == None);
+		assert!(index.line_range(1) == Some(0..5));
+		assert!(index.line_range(2) == Some(6..11));
+		assert!(index.line_range(3) == Some(12..12));
+		assert!(index.line_range(4) == None);
+
+		let index = LineIndex::new(b"", LineEndings::default());
+		assert!(index.line_count() == 1);
+		assert!(index.line_range(1) == Some(0..0));
+
+		let index = LineIndex::new(b"hello\r\nworld\r\n", LineEndings::default());
+		assert!(index.line_count() == 3);
+		assert!(index.line_range(1) == Some(0..5));
+		assert!(index.line_range(2) == Some(7..12));
+		assert!(index.line_range(3) == Some(14..14));
+	}
+
+	#[test]
+	fn test_line_at() {
+		let index = LineIndex::new(b"a\r\nb\n\nc", LineEndings::default());
+		assert!(index.line_at(0) == 1);
+		assert!(index.line_at(1) == 1);
+		assert!(index.line_at(2) == 1);
+		assert!(index.line_at(3) == 2);
+		assert!(index.line_at(4) == 2);
+		assert!(index.line_at(5) == 3);
+		assert!(index.line_at(6) == 4);
+	}
+
+	#[test]
+	fn test_line_endings() {
+		let data = "a\nb\r\nc\rd\u{2028}e\u{2029}f\u{2027}g".as_bytes();
+		let unicode_lines: Vec<&[u8]> = vec![b"a", b"b", b"c", b"d", b"e", "f\u{2027}g".as_bytes()];
+		assert!(
+			lines(data, LineEndings::Lf)
+				== [
+					&b"a"[..],
+					"b\r".as_bytes(),
+					"c\rd\u{2028}e\u{2029}f\u{2027}g".as_bytes()
+				]
+		);
+		assert!(lines(data, LineEndings::LfCrLf) == [&b"a"[..], b"b", "c\rd\u{2028}e\u{2029}f\u{2027}g".as_bytes()]);
+		assert!(lines(data, LineEndings::Any) == [&b"a"[..], b"b", b"c", "d\u{2028}e\u{2029}f\u{2027}g".as_bytes()]);
+		assert!(lines(data, LineEndings::Unicode) == unicode_lines);
+
+		// A lone CR at the end of the data, and an incomplete LS sequence.
+		assert!(lines(b"a\r", LineEndings::LfCrLf) == [&b"a\r"[..]]);
+		assert!(lines(b"a\r", LineEndings::Any) == [&b"a"[..], b""]);
+		assert!(lines(b"a\xE2\x80", LineEndings::Unicode) == [&b"a\xE2\x80"[..]]);
+	}
+
+	#[test]
+	fn test_location() {
+		let index = LineIndex::new(b"a\r\nb\rc\n", LineEndings::Lf);
+		assert!(index.location(1) == (1, 2));
+		assert!(index.location(2) == (1, 3));
+		assert!(index.location(3) == (2, 1));
+		assert!(index.location(4) == (2, 2));
+		assert!(index.location(5) == (2, 3));
+
+		let index = LineIndex::new(b"a\r\nb\rc\n", LineEndings::LfCrLf);
+		assert!(index.location(1) == (1, 2));
+		assert!(index.location(2) == (1, 2));
+		assert!(index.location(3) == (2, 1));
+		assert!(index.location(4) == (2, 2));
+		assert!(index.location(5) == (2, 3));
+
+		let index = LineIndex::new(b"a\r\nb\rc\n", LineEndings::Any);
+		assert!(index.location(1) == (1, 2));
+		assert!(index.location(2) == (1, 2));
+		assert!(index.location(3) == (2, 1));
+		assert!(index.location(4) == (2, 2));
+		assert!(index.location(5) == (3, 1));
+		assert!(index.location(6) == (3, 2));
+		assert!(index.location(7) == (4, 1));
+
+		let index = LineIndex::new("a\u{2028}b".as_bytes(), LineEndings::Unicode);
+		assert!(index.location(1) == (1, 2));
+		assert!(index.location(2) == (1, 2));
+		assert!(index.location(3) == (1, 2));
+		assert!(index.location(4) == (2, 1));
+	}
+}
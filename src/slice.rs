@@ -21,6 +21,9 @@
 // OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::path::{Path, PathBuf};
+
 /// Generalization of slices and str.
 pub trait Slice {
 	type Element;
@@ -61,6 +64,45 @@ impl Slice for str {
 	}
 }
 
+/// The elements of an `OsStr` are the bytes of its platform-specific encoding.
+impl Slice for OsStr {
+	type Element = u8;
+
+	fn len(&self) -> usize {
+		self.as_encoded_bytes().len()
+	}
+
+	fn start_ptr(&self) -> *const Self::Element {
+		self.as_encoded_bytes().as_ptr()
+	}
+}
+
+/// The elements of a `Path` are the bytes of its platform-specific encoding, like for [`OsStr`].
+impl Slice for Path {
+	type Element = u8;
+
+	fn len(&self) -> usize {
+		self.as_os_str().len()
+	}
+
+	fn start_ptr(&self) -> *const Self::Element {
+		self.as_os_str().start_ptr()
+	}
+}
+
+/// The elements of a `CStr` are its bytes, excluding the nul terminator.
+impl Slice for CStr {
+	type Element = u8;
+
+	fn len(&self) -> usize {
+		self.to_bytes().len()
+	}
+
+	fn start_ptr(&self) -> *const Self::Element {
+		self.to_bytes().as_ptr()
+	}
+}
+
 /// Trait for things that can be borrowed as a slice, including slices themselves.
 pub trait BorrowSlice {
 	type Slice: Slice + ?Sized;
@@ -92,7 +134,7 @@ impl<'a, T: 'a> BorrowSlice for &'a [T] {
 	}
 }
 
-impl<'a> BorrowSlice for &'a str {
+impl BorrowSlice for &str {
 	type Slice = str;
 
 	fn borrow_slice(&self) -> &str {
@@ -139,3 +181,60 @@ impl<T> BorrowSlice for std::sync::Arc<[T]> {
 		self
 	}
 }
+
+impl BorrowSlice for &OsStr {
+	type Slice = OsStr;
+
+	fn borrow_slice(&self) -> &OsStr {
+		self
+	}
+}
+
+impl BorrowSlice for OsString {
+	type Slice = OsStr;
+
+	fn borrow_slice(&self) -> &OsStr {
+		self
+	}
+}
+
+impl BorrowSlice for &Path {
+	type Slice = Path;
+
+	fn borrow_slice(&self) -> &Path {
+		self
+	}
+}
+
+impl BorrowSlice for PathBuf {
+	type Slice = Path;
+
+	fn borrow_slice(&self) -> &Path {
+		self
+	}
+}
+
+impl BorrowSlice for &CStr {
+	type Slice = CStr;
+
+	fn borrow_slice(&self) -> &CStr {
+		self
+	}
+}
+
+impl BorrowSlice for CString {
+	type Slice = CStr;
+
+	fn borrow_slice(&self) -> &CStr {
+		self
+	}
+}
+
+#[cfg(feature = "mmap")]
+impl BorrowSlice for memmap2::Mmap {
+	type Slice = [u8];
+
+	fn borrow_slice(&self) -> &[u8] {
+		self
+	}
+}
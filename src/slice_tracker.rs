@@ -25,6 +25,7 @@ use std::cell::UnsafeCell;
 use std::collections::btree_map;
 use std::collections::BTreeMap;
 use std::collections::Bound::{Excluded, Included, Unbounded};
+use std::fmt::Debug;
 
 use super::BorrowSlice;
 use super::Slice;
@@ -41,6 +42,9 @@ pub struct Entry<Data, Metadata> {
 	meta: Box<Metadata>,
 }
 
+/// Pointer to the first element of the slice borrowed from `Data`.
+type ElementPtr<Data> = *const <<Data as BorrowSlice>::Slice as Slice>::Element;
+
 /// Tracker for slices with metadata.
 ///
 /// The tracker can take ownership or store references if their lifetime is long enough.
@@ -52,7 +56,7 @@ pub struct SliceTracker<Data, Metadata>
 where
 	Data: BorrowSlice + StableBorrow,
 {
-	map: UnsafeCell<BTreeMap<*const <Data::Slice as Slice>::Element, Entry<Data, Metadata>>>,
+	map: UnsafeCell<BTreeMap<ElementPtr<Data>, Entry<Data, Metadata>>>,
 }
 
 impl<Data, Metadata> Default for SliceTracker<Data, Metadata>
@@ -76,10 +80,13 @@ where
 	}
 
 	/// Insert a slice with metadata without checking if the data is already present.
-	pub unsafe fn insert_unsafe<'path>(&self, data: Data, meta: impl Into<Box<Metadata>>) -> &Data::Slice {
+	///
+	/// # Safety
+	/// The data must not be empty and it must not overlap with any slice that is already tracked.
+	pub unsafe fn insert_unsafe(&self, data: Data, meta: impl Into<Box<Metadata>>) -> &Data::Slice {
 		// Insert the data itself.
 		match self.map_mut().entry(data.start_ptr()) {
-			btree_map::Entry::Vacant(x) => &x
+			btree_map::Entry::Vacant(x) => x
 				.insert(Entry {
 					data,
 					meta: meta.into(),
@@ -91,7 +98,8 @@ where
 	}
 
 	/// Safely insert a slice with metadata.
-	pub fn insert<'path>(&self, data: Data, meta: impl Into<Box<Metadata>>) -> Result<&Data::Slice, ()> {
+	#[allow(clippy::result_unit_err)]
+	pub fn insert(&self, data: Data, meta: impl Into<Box<Metadata>>) -> Result<&Data::Slice, ()> {
 		// Reject empty data or data that is already (partially) tracked.
 		if data.is_empty() || self.has_overlap(data.borrow_slice()) {
 			return Err(());
@@ -110,6 +118,14 @@ where
 			.map(|entry| (entry.data.borrow_slice(), entry.meta.as_ref()))
 	}
 
+	/// Get the whole tracked slice and metadata for a (partial) slice given as start pointer and length.
+	///
+	/// The pointer is only compared against the tracked slices, it is never dereferenced.
+	pub(crate) fn get_raw(&self, start: ElementPtr<Data>, len: usize) -> Option<(&Data::Slice, &Metadata)> {
+		self.get_entry_raw(start, len)
+			.map(|entry| (entry.data.borrow_slice(), entry.meta.as_ref()))
+	}
+
 	/// Get the metadata for a (partial) slice.
 	pub fn metadata(&self, data: &Data::Slice) -> Option<&Metadata> {
 		self.get_entry(data).map(|entry| entry.meta.as_ref())
@@ -120,40 +136,66 @@ where
 		self.get_entry(data).map(|entry| entry.data.borrow_slice())
 	}
 
+	/// Remove the tracked slice containing a (partial) slice given as start pointer and length.
+	///
+	/// Returns the data and metadata of the removed slice.
+	/// This requires exclusive access to the tracker, so no references to the removed data can remain.
+	pub(crate) fn remove_raw(&mut self, start: ElementPtr<Data>, len: usize) -> Option<(Data, Metadata)> {
+		let key = self.get_entry_raw(start, len)?.data.start_ptr();
+		let entry = self.map.get_mut().remove(&key)?;
+		Some((entry.data, *entry.meta))
+	}
+
+	/// Iterate over the tracked slices and their metadata, ordered by address.
+	pub(crate) fn iter(&self) -> impl Iterator<Item = (&Data::Slice, &Metadata)> {
+		self.map()
+			.values()
+			.map(|entry| (entry.data.borrow_slice(), entry.meta.as_ref()))
+	}
+
 	/// Get the map from the UnsafeCell.
-	fn map(&self) -> &BTreeMap<*const <Data::Slice as Slice>::Element, Entry<Data, Metadata>> {
+	fn map(&self) -> &BTreeMap<ElementPtr<Data>, Entry<Data, Metadata>> {
 		unsafe { &*self.map.get() }
 	}
 
 	/// Get the map from the UnsafeCell as mutable map.
-	fn map_mut(&self) -> &mut BTreeMap<*const <Data::Slice as Slice>::Element, Entry<Data, Metadata>> {
+	///
+	/// Entries are only ever added through a shared reference, never removed or modified,
+	/// so references handed out earlier remain valid.
+	#[allow(clippy::mut_from_ref)]
+	fn map_mut(&self) -> &mut BTreeMap<ElementPtr<Data>, Entry<Data, Metadata>> {
 		unsafe { &mut *self.map.get() }
 	}
 
 	/// Find the last entry with start_ptr <= the given bound.
-	fn last_at_or_before(&self, bound: *const <Data::Slice as Slice>::Element) -> Option<&Entry<Data, Metadata>> {
+	fn last_at_or_before(&self, bound: ElementPtr<Data>) -> Option<&Entry<Data, Metadata>> {
 		let (_key, value) = self.map().range((Unbounded, Included(bound))).next_back()?;
-		Some(&value)
+		Some(value)
 	}
 
 	/// Find the last entry with start_ptr < the given bound.
-	fn last_before(&self, bound: *const <Data::Slice as Slice>::Element) -> Option<&Entry<Data, Metadata>> {
+	fn last_before(&self, bound: ElementPtr<Data>) -> Option<&Entry<Data, Metadata>> {
 		let (_key, value) = self.map().range((Unbounded, Excluded(bound))).next_back()?;
-		Some(&value)
+		Some(value)
 	}
 
 	/// Get the tracking entry for a slice.
 	fn get_entry(&self, data: &Data::Slice) -> Option<&Entry<Data, Metadata>> {
+		self.get_entry_raw(data.start_ptr(), data.len())
+	}
+
+	/// Get the tracking entry for a slice given as start pointer and length.
+	fn get_entry_raw(&self, start: ElementPtr<Data>, len: usize) -> Option<&Entry<Data, Metadata>> {
 		// Empty slices can not be tracked.
 		// They can't be distuingished from str_a[end..end] or str_b[0..0],
 		// if str_a and str_b directly follow eachother in memory.
-		if data.is_empty() {
+		if len == 0 {
 			return None;
 		}
 
-		// Get the last element where start_ptr <= data.start_ptr
-		let entry = self.last_at_or_before(data.start_ptr())?;
-		if data.end_ptr() <= entry.data.borrow_slice().end_ptr() {
+		// Get the last element where start_ptr <= start
+		let entry = self.last_at_or_before(start)?;
+		if start.wrapping_add(len) <= entry.data.borrow_slice().end_ptr() {
 			Some(entry)
 		} else {
 			None
@@ -179,11 +221,35 @@ where
 	}
 }
 
+impl<Data, Metadata> Debug for Entry<Data, Metadata>
+where
+	Data: BorrowSlice,
+	Metadata: Debug,
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("Entry")
+			.field("len", &self.data.len())
+			.field("meta", &self.meta)
+			.finish()
+	}
+}
+
+impl<Data, Metadata> Debug for SliceTracker<Data, Metadata>
+where
+	Data: BorrowSlice + StableBorrow,
+	Metadata: Debug,
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_list().entries(self.map().values()).finish()
+	}
+}
+
 #[cfg(test)]
 mod test {
 	use super::*;
 
 	#[test]
+	#[allow(clippy::bool_assert_comparison, clippy::redundant_slicing)]
 	fn test_insert() {
 		let pool = SliceTracker::<&str, ()>::default();
 		let data = "aap noot mies";
@@ -213,6 +279,42 @@ mod test {
 		assert!(std::ptr::eq(data, pool.whole_slice(&data[..]).unwrap()));
 	}
 
+	#[test]
+	fn test_insert_os_types() {
+		use std::ffi::{CStr, CString, OsStr, OsString};
+		use std::path::{Path, PathBuf};
+
+		let pool = SliceTracker::<OsString, u32>::default();
+		let arg = pool.insert(OsString::from("--input=foo.txt"), 1).unwrap();
+		let (_, value) = arg.to_str().unwrap().split_at(8);
+		assert!(std::ptr::eq(arg, pool.whole_slice(OsStr::new(value)).unwrap()));
+		assert!(pool.get(OsStr::new(value)) == Some((arg, &1)));
+		assert!(!pool.is_tracked(OsStr::new("--input=foo.txt")));
+
+		let pool = SliceTracker::<PathBuf, ()>::default();
+		let path = pool.insert(PathBuf::from("src/main.rs"), ()).unwrap();
+		assert!(std::ptr::eq(path, pool.whole_slice(path.parent().unwrap()).unwrap()));
+		assert!(std::ptr::eq(
+			path,
+			pool.whole_slice(Path::new(path.file_name().unwrap())).unwrap()
+		));
+		assert!(pool.insert(PathBuf::new(), ()).is_err());
+
+		let pool = SliceTracker::<&Path, ()>::default();
+		let path = Path::new("src/lib.rs");
+		assert!(std::ptr::eq(path, pool.insert(path, ()).unwrap()));
+
+		let pool = SliceTracker::<CString, ()>::default();
+		let name = pool.insert(CString::new("hello").unwrap(), ()).unwrap();
+		let tail = CStr::from_bytes_with_nul(&name.to_bytes_with_nul()[2..]).unwrap();
+		assert!(std::ptr::eq(name, pool.whole_slice(tail).unwrap()));
+		assert!(pool.insert(CString::default(), ()).is_err());
+
+		let pool = SliceTracker::<&CStr, ()>::default();
+		let name = CStr::from_bytes_with_nul(b"world\0").unwrap();
+		assert!(std::ptr::eq(name, pool.insert(name, ()).unwrap()));
+	}
+
 	#[test]
 	fn test_insert_part() {
 		let pool = SliceTracker::<&str, ()>::default();
@@ -239,4 +341,20 @@ mod test {
 		assert!(std::ptr::eq(noot, pool.whole_slice(&data[5..8]).unwrap()));
 		assert!(std::ptr::eq(noot, pool.whole_slice(&data[5..7]).unwrap()));
 	}
+
+	#[test]
+	fn test_remove() {
+		let mut pool = SliceTracker::<&str, i32>::default();
+		let data = "aap noot mies";
+		pool.insert(&data[0..3], 1).unwrap();
+		pool.insert(&data[4..8], 2).unwrap();
+
+		assert!(pool.remove_raw(data[5..6].as_ptr(), 1) == Some(("noot", 2)));
+		assert!(!pool.is_tracked(&data[4..8]));
+		assert!(pool.remove_raw(data[5..6].as_ptr(), 1).is_none());
+		assert!(pool.metadata(&data[0..3]) == Some(&1));
+
+		// The removed slice can be inserted again.
+		assert!(pool.insert(&data[4..8], 3).is_ok());
+	}
 }
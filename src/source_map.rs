@@ -0,0 +1,672 @@
+// Copyright (c) 2018, Maarten de Vries <maarten@de-vri.es>
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//   list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//   this list of conditions and the following disclaimer in the documentation
+//   and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::convert::TryFrom;
+use std::io::{Error, ErrorKind};
+
+use crate::line_index::LineIndex;
+
+/// A fragment of built data, as offset in the data and start pointer and length of the tracked slice.
+type Fragment = (usize, (*const u8, usize));
+
+/// Builder for a string made of fragments of tracked data.
+///
+/// Each fragment appended with [`Self::push`] records the tracked slice it was copied from.
+/// After inserting the string with [`FileTracker::insert_mapped`](crate::FileTracker::insert_mapped),
+/// the source location of any slice of it is the location of the data it was copied from,
+/// similar to a JavaScript source map.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMapBuilder {
+	/// The data built so far.
+	data: String,
+
+	/// The fragments of the data, sorted by offset.
+	fragments: Vec<Fragment>,
+}
+
+impl SourceMapBuilder {
+	/// Create a new empty builder.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Append a fragment of tracked data.
+	///
+	/// The fragment must be tracked by the tracker the data is inserted in,
+	/// but this is not checked until the data is inserted.
+	pub fn push(&mut self, fragment: &str) {
+		if fragment.is_empty() {
+			return;
+		}
+		let offset = self.data.len();
+		self.data.push_str(fragment);
+		self.fragments.push((offset, (fragment.as_ptr(), fragment.len())));
+	}
+
+	/// Append text that did not come from tracked data.
+	///
+	/// Slices of the text have an unknown source location.
+	pub fn push_unmapped(&mut self, text: &str) {
+		self.data.push_str(text);
+	}
+
+	/// Get the data built so far.
+	pub fn as_str(&self) -> &str {
+		&self.data
+	}
+
+	/// Get the length of the data built so far in bytes.
+	pub fn len(&self) -> usize {
+		self.data.len()
+	}
+
+	/// Check if the data built so far is empty.
+	pub fn is_empty(&self) -> bool {
+		self.data.is_empty()
+	}
+
+	/// Split the builder into the data and the fragments.
+	pub(crate) fn into_parts(self) -> (String, Vec<Fragment>) {
+		(self.data, self.fragments)
+	}
+}
+
+/// A Source Map v3, with the mappings decoded.
+///
+/// Positions are zero-based, and columns are counted in UTF-16 code units, as in the source map format.
+/// Names are not supported and are ignored when parsing.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct SourceMap {
+	/// The name of the generated file.
+	pub file: Option<String>,
+
+	/// The root that source paths are relative to.
+	pub source_root: Option<String>,
+
+	/// The paths of the original files.
+	pub sources: Vec<String>,
+
+	/// The contents of the original files, if included in the source map.
+	pub sources_content: Vec<Option<String>>,
+
+	/// The segments of each line of the generated file.
+	pub mappings: Vec<Vec<Segment>>,
+}
+
+/// A mapping from a column in the generated file to a position in an original file.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Segment {
+	/// The column in the generated file.
+	pub column: usize,
+
+	/// The index of the original file, and the line and column in it, or `None` for unmapped data.
+	pub source: Option<(usize, usize, usize)>,
+}
+
+impl SourceMap {
+	/// Parse a source map from JSON.
+	///
+	/// Fails with [`ErrorKind::InvalidData`] if the JSON is not a valid Source Map v3.
+	/// Index maps with sections are not supported.
+	pub fn parse(json: &str) -> std::io::Result<Self> {
+		let mut parser = JsonParser {
+			data: json.as_bytes(),
+			pos: 0,
+			depth: 0,
+		};
+		let value = parser.parse_value()?;
+		parser.skip_whitespace();
+		if parser.pos != parser.data.len() {
+			return Err(invalid_data("trailing data after JSON value"));
+		}
+
+		let fields = match value {
+			Json::Object(fields) => fields,
+			_ => return Err(invalid_data("source map is not a JSON object")),
+		};
+		let field = |name: &str| fields.iter().find(|(key, _)| key == name).map(|(_, value)| value);
+		let string = |value: &Json| match value {
+			Json::String(value) => Ok(Some(value.clone())),
+			Json::Null => Ok(None),
+			_ => Err(invalid_data("expected a string in source map")),
+		};
+		let strings = |name: &str| match field(name) {
+			None => Ok(Vec::new()),
+			Some(Json::Array(values)) => values.iter().map(string).collect(),
+			Some(_) => Err(invalid_data(format!(
+				"expected an array for \"{}\" in source map",
+				name
+			))),
+		};
+
+		if field("version") != Some(&Json::Number(3.0)) {
+			return Err(invalid_data("unsupported source map version"));
+		}
+		if field("sections").is_some() {
+			return Err(invalid_data("index maps are not supported"));
+		}
+		let sources = strings("sources")?
+			.into_iter()
+			.map(|source| source.ok_or_else(|| invalid_data("source path is null")))
+			.collect::<std::io::Result<_>>()?;
+		let mappings = match field("mappings") {
+			Some(Json::String(mappings)) => decode_mappings(mappings)?,
+			_ => return Err(invalid_data("source map has no mappings")),
+		};
+		Ok(Self {
+			file: field("file").map(string).transpose()?.flatten(),
+			source_root: field("sourceRoot").map(string).transpose()?.flatten(),
+			sources,
+			sources_content: strings("sourcesContent")?,
+			mappings,
+		})
+	}
+
+	/// Serialize the source map as JSON.
+	pub fn to_json(&self) -> String {
+		let mut json = String::from("{\"version\":3");
+		if let Some(file) = &self.file {
+			json.push_str(",\"file\":");
+			write_json_string(&mut json, file);
+		}
+		if let Some(source_root) = &self.source_root {
+			json.push_str(",\"sourceRoot\":");
+			write_json_string(&mut json, source_root);
+		}
+		json.push_str(",\"sources\":[");
+		for (i, source) in self.sources.iter().enumerate() {
+			if i > 0 {
+				json.push(',');
+			}
+			write_json_string(&mut json, source);
+		}
+		json.push_str("],\"sourcesContent\":[");
+		for (i, content) in self.sources_content.iter().enumerate() {
+			if i > 0 {
+				json.push(',');
+			}
+			match content {
+				Some(content) => write_json_string(&mut json, content),
+				None => json.push_str("null"),
+			}
+		}
+		json.push_str("],\"names\":[],\"mappings\":\"");
+		json.push_str(&encode_mappings(&self.mappings));
+		json.push_str("\"}");
+		json
+	}
+}
+
+/// Get the zero-based line and UTF-16 column of a byte offset in text.
+///
+/// The line index must use [`LineEndings::Unicode`](crate::LineEndings::Unicode), like source maps do.
+pub(crate) fn position(text: &str, lines: &LineIndex, offset: usize) -> (usize, usize) {
+	let line = lines.line_at(offset);
+	let range = lines.line_range(line).unwrap();
+	let column = text[range.start..offset.min(range.end)].encode_utf16().count();
+	(line - 1, column)
+}
+
+/// Get the byte offset of a zero-based line and UTF-16 column in text.
+///
+/// Columns past the end of the line are clamped to the end of the line.
+/// Returns `None` if the line is out of range.
+pub(crate) fn offset(text: &str, lines: &LineIndex, line: usize, column: usize) -> Option<usize> {
+	let range = lines.line_range(line + 1)?;
+	let mut utf16_column = 0;
+	for (i, c) in text[range.clone()].char_indices() {
+		if utf16_column >= column {
+			return Some(range.start + i);
+		}
+		utf16_column += c.len_utf16();
+	}
+	Some(range.end)
+}
+
+fn invalid_data(message: impl Into<String>) -> Error {
+	Error::new(ErrorKind::InvalidData, message.into())
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Append a value to a string as base64 VLQ.
+fn encode_vlq(output: &mut String, value: i64) {
+	let mut value = if value < 0 { (-value << 1) | 1 } else { value << 1 };
+	loop {
+		let mut digit = value & 0x1F;
+		value >>= 5;
+		if value > 0 {
+			digit |= 0x20;
+		}
+		output.push(BASE64[digit as usize] as char);
+		if value == 0 {
+			break;
+		}
+	}
+}
+
+/// Decode the base64 VLQ values of a segment.
+fn decode_vlq(segment: &str) -> std::io::Result<Vec<i64>> {
+	let mut values = Vec::new();
+	let mut value = 0i64;
+	let mut shift = 0;
+	for byte in segment.bytes() {
+		let digit = BASE64
+			.iter()
+			.position(|&c| c == byte)
+			.ok_or_else(|| invalid_data("invalid base64 character in source map mappings"))? as i64;
+		if shift > 60 {
+			return Err(invalid_data("VLQ value out of range in source map mappings"));
+		}
+		value |= (digit & 0x1F) << shift;
+		shift += 5;
+		if digit & 0x20 == 0 {
+			values.push(if value & 1 == 1 { -(value >> 1) } else { value >> 1 });
+			value = 0;
+			shift = 0;
+		}
+	}
+	if shift != 0 {
+		return Err(invalid_data("incomplete VLQ value in source map mappings"));
+	}
+	Ok(values)
+}
+
+/// Encode the segments of each generated line as a mappings string.
+fn encode_mappings(lines: &[Vec<Segment>]) -> String {
+	let mut output = String::new();
+	let (mut source, mut line, mut column) = (0, 0, 0);
+	for (i, segments) in lines.iter().enumerate() {
+		if i > 0 {
+			output.push(';');
+		}
+		let mut generated_column = 0;
+		for (j, segment) in segments.iter().enumerate() {
+			if j > 0 {
+				output.push(',');
+			}
+			encode_vlq(&mut output, segment.column as i64 - generated_column);
+			generated_column = segment.column as i64;
+			if let Some((next_source, next_line, next_column)) = segment.source {
+				encode_vlq(&mut output, next_source as i64 - source);
+				encode_vlq(&mut output, next_line as i64 - line);
+				encode_vlq(&mut output, next_column as i64 - column);
+				(source, line, column) = (next_source as i64, next_line as i64, next_column as i64);
+			}
+		}
+	}
+	output
+}
+
+/// Decode a mappings string into the segments of each generated line.
+fn decode_mappings(mappings: &str) -> std::io::Result<Vec<Vec<Segment>>> {
+	let (mut source, mut line, mut column) = (0i64, 0i64, 0i64);
+	let to_usize = |value: i64| usize::try_from(value).map_err(|_| invalid_data("negative position in source map"));
+	let add = |value: i64, delta: i64| {
+		value
+			.checked_add(delta)
+			.ok_or_else(|| invalid_data("position out of range in source map"))
+	};
+	mappings
+		.split(';')
+		.map(|segments| {
+			let mut generated_column = 0i64;
+			segments
+				.split(',')
+				.filter(|segment| !segment.is_empty())
+				.map(|segment| {
+					let values = decode_vlq(segment)?;
+					generated_column = add(generated_column, values[0])?;
+					let source = match values.len() {
+						1 => None,
+						4 | 5 => {
+							source = add(source, values[1])?;
+							line = add(line, values[2])?;
+							column = add(column, values[3])?;
+							Some((to_usize(source)?, to_usize(line)?, to_usize(column)?))
+						}
+						_ => return Err(invalid_data("invalid segment length in source map mappings")),
+					};
+					Ok(Segment {
+						column: to_usize(generated_column)?,
+						source,
+					})
+				})
+				.collect()
+		})
+		.collect()
+}
+
+/// Append a string to JSON output as a JSON string.
+fn write_json_string(output: &mut String, value: &str) {
+	output.push('"');
+	for c in value.chars() {
+		match c {
+			'"' => output.push_str("\\\""),
+			'\\' => output.push_str("\\\\"),
+			'\n' => output.push_str("\\n"),
+			'\r' => output.push_str("\\r"),
+			'\t' => output.push_str("\\t"),
+			c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+			c => output.push(c),
+		}
+	}
+	output.push('"');
+}
+
+/// A parsed JSON value.
+#[derive(Debug, PartialEq)]
+enum Json {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>),
+}
+
+/// The maximum nesting depth of arrays and objects accepted by [`JsonParser`].
+const MAX_JSON_DEPTH: usize = 64;
+
+/// A minimal JSON parser, for reading source maps.
+struct JsonParser<'a> {
+	data: &'a [u8],
+	pos: usize,
+
+	/// The number of arrays and objects the parser is currently in.
+	depth: usize,
+}
+
+impl JsonParser<'_> {
+	fn skip_whitespace(&mut self) {
+		while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.data.get(self.pos) {
+			self.pos += 1;
+		}
+	}
+
+	fn expect(&mut self, token: &str) -> std::io::Result<()> {
+		if self.data[self.pos..].starts_with(token.as_bytes()) {
+			self.pos += token.len();
+			Ok(())
+		} else {
+			Err(invalid_data(format!(
+				"expected `{}` at byte {} of JSON",
+				token, self.pos
+			)))
+		}
+	}
+
+	fn parse_value(&mut self) -> std::io::Result<Json> {
+		self.skip_whitespace();
+		match self.data.get(self.pos) {
+			Some(b'n') => self.expect("null").map(|()| Json::Null),
+			Some(b't') => self.expect("true").map(|()| Json::Bool(true)),
+			Some(b'f') => self.expect("false").map(|()| Json::Bool(false)),
+			Some(b'"') => self.parse_string().map(Json::String),
+			Some(b'[') => self.parse_nested(Self::parse_array),
+			Some(b'{') => self.parse_nested(Self::parse_object),
+			Some(b'-' | b'0'..=b'9') => {
+				let start = self.pos;
+				while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.data.get(self.pos) {
+					self.pos += 1;
+				}
+				let number = std::str::from_utf8(&self.data[start..self.pos]).unwrap();
+				number
+					.parse()
+					.map(Json::Number)
+					.map_err(|_| invalid_data(format!("invalid number at byte {} of JSON", start)))
+			}
+			_ => Err(invalid_data(format!("expected a value at byte {} of JSON", self.pos))),
+		}
+	}
+
+	/// Parse an array or object, failing if it is nested more than [`MAX_JSON_DEPTH`] levels deep.
+	fn parse_nested(&mut self, parse: fn(&mut Self) -> std::io::Result<Json>) -> std::io::Result<Json> {
+		if self.depth == MAX_JSON_DEPTH {
+			return Err(invalid_data(format!("JSON is nested too deeply at byte {}", self.pos)));
+		}
+		self.depth += 1;
+		let value = parse(self);
+		self.depth -= 1;
+		value
+	}
+
+	fn parse_array(&mut self) -> std::io::Result<Json> {
+		self.pos += 1;
+		let mut values = Vec::new();
+		self.skip_whitespace();
+		if self.data.get(self.pos) == Some(&b']') {
+			self.pos += 1;
+			return Ok(Json::Array(values));
+		}
+		loop {
+			values.push(self.parse_value()?);
+			self.skip_whitespace();
+			match self.data.get(self.pos) {
+				Some(b',') => self.pos += 1,
+				_ => return self.expect("]").map(|()| Json::Array(values)),
+			}
+		}
+	}
+
+	fn parse_object(&mut self) -> std::io::Result<Json> {
+		self.pos += 1;
+		let mut fields = Vec::new();
+		self.skip_whitespace();
+		if self.data.get(self.pos) == Some(&b'}') {
+			self.pos += 1;
+			return Ok(Json::Object(fields));
+		}
+		loop {
+			self.skip_whitespace();
+			let key = self.parse_string()?;
+			self.skip_whitespace();
+			self.expect(":")?;
+			fields.push((key, self.parse_value()?));
+			self.skip_whitespace();
+			match self.data.get(self.pos) {
+				Some(b',') => self.pos += 1,
+				_ => return self.expect("}").map(|()| Json::Object(fields)),
+			}
+		}
+	}
+
+	fn parse_string(&mut self) -> std::io::Result<String> {
+		self.expect("\"")?;
+		let mut value = Vec::new();
+		loop {
+			match self.data.get(self.pos) {
+				None => return Err(invalid_data("unterminated string in JSON")),
+				Some(b'"') => {
+					self.pos += 1;
+					// The input is a `str` and escapes produce valid UTF-8, so this can not fail.
+					return Ok(String::from_utf8(value).unwrap());
+				}
+				Some(b'\\') => {
+					let escape = self.data.get(self.pos + 1).copied();
+					self.pos += 2;
+					let c = match escape {
+						Some(b'"') => '"',
+						Some(b'\\') => '\\',
+						Some(b'/') => '/',
+						Some(b'b') => '\u{8}',
+						Some(b'f') => '\u{c}',
+						Some(b'n') => '\n',
+						Some(b'r') => '\r',
+						Some(b't') => '\t',
+						Some(b'u') => self.parse_unicode_escape()?,
+						_ => return Err(invalid_data(format!("invalid escape at byte {} of JSON", self.pos - 2))),
+					};
+					value.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+				}
+				Some(&byte) => {
+					value.push(byte);
+					self.pos += 1;
+				}
+			}
+		}
+	}
+
+	/// Parse the hex digits of a `\u` escape, and a following low surrogate escape if needed.
+	fn parse_unicode_escape(&mut self) -> std::io::Result<char> {
+		let high = self.parse_hex4()?;
+		let code = if (0xD800..0xDC00).contains(&high) {
+			self.expect("\\u")?;
+			let low = self.parse_hex4()?;
+			if !(0xDC00..0xE000).contains(&low) {
+				return Err(invalid_data("invalid surrogate pair in JSON string"));
+			}
+			0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+		} else {
+			high
+		};
+		char::from_u32(code).ok_or_else(|| invalid_data("invalid unicode escape in JSON string"))
+	}
+
+	fn parse_hex4(&mut self) -> std::io::Result<u32> {
+		let digits = self
+			.data
+			.get(self.pos..self.pos + 4)
+			.filter(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+			.and_then(|digits| std::str::from_utf8(digits).ok())
+			.and_then(|digits| u32::from_str_radix(digits, 16).ok())
+			.ok_or_else(|| invalid_data(format!("invalid unicode escape at byte {} of JSON", self.pos)))?;
+		self.pos += 4;
+		Ok(digits)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use assert2::assert;
+
+	#[test]
+	fn test_push() {
+		let text = "hello world";
+		let mut builder = SourceMapBuilder::new();
+		builder.push(&text[..5]);
+		builder.push(&text[5..]);
+		builder.push_unmapped("!");
+		builder.push("");
+		builder.push(&text[..5]);
+		assert!(builder.as_str() == "hello world!hello");
+		assert!(builder.len() == 17);
+
+		let (data, fragments) = builder.into_parts();
+		assert!(data == "hello world!hello");
+		assert!(
+			fragments
+				== [
+					(0, (text.as_ptr(), 5)),
+					(5, (text[5..].as_ptr(), 6)),
+					(12, (text.as_ptr(), 5))
+				]
+		);
+	}
+
+	#[test]
+	fn test_vlq() {
+		let mut output = String::new();
+		for value in [0, 1, -1, 15, 16, -16, 1000, -123456] {
+			output.clear();
+			encode_vlq(&mut output, value);
+			assert!(decode_vlq(&output).unwrap() == [value]);
+		}
+		output.clear();
+		encode_vlq(&mut output, 16);
+		assert!(output == "gB");
+		assert!(decode_vlq("AAgBC").unwrap() == [0, 0, 16, 1]);
+		assert!(decode_vlq("g").is_err());
+		assert!(decode_vlq("A!").is_err());
+	}
+
+	#[test]
+	fn test_parse_source_map() {
+		let json = r#"{
+			"version": 3,
+			"file": "out.js",
+			"sourceRoot": "src",
+			"sources": ["a.js", "bé.js"],
+			"sourcesContent": ["let a;\n", null],
+			"names": ["a"],
+			"mappings": "AAAA,IAAIA;;ACAJ,C"
+		}"#;
+		let map = SourceMap::parse(json).unwrap();
+		assert!(map.file.as_deref() == Some("out.js"));
+		assert!(map.source_root.as_deref() == Some("src"));
+		assert!(&map.sources == &["a.js", "bé.js"]);
+		assert!(&map.sources_content == &[Some(String::from("let a;\n")), None]);
+		let segment = |column, source| Segment { column, source };
+		assert!(
+			&map.mappings
+				== &[
+					vec![segment(0, Some((0, 0, 0))), segment(4, Some((0, 0, 4)))],
+					vec![],
+					vec![segment(0, Some((1, 0, 0))), segment(1, None)],
+				]
+		);
+
+		let map = SourceMap::parse(&map.to_json()).unwrap();
+		assert!(&map.sources == &["a.js", "bé.js"]);
+		assert!(map.mappings.len() == 3);
+		assert!(&map.mappings[2] == &[segment(0, Some((1, 0, 0))), segment(1, None)]);
+
+		let error = SourceMap::parse(r#"{"version": 2, "mappings": ""}"#).unwrap_err();
+		assert!(error.kind() == ErrorKind::InvalidData);
+		assert!(SourceMap::parse(r#"{"version": 3, "mappings": "A"#).is_err());
+		assert!(SourceMap::parse(r#"{"version": 3, "mappings": "AA"}"#).is_err());
+
+		// Deeply nested JSON and positions that overflow are rejected.
+		let nested = format!(
+			r#"{{"version": 3, "mappings": "", "x": {}{}}}"#,
+			"[".repeat(100_000),
+			"]".repeat(100_000)
+		);
+		assert!(SourceMap::parse(&nested).unwrap_err().kind() == ErrorKind::InvalidData);
+		let nested = format!(
+			r#"{{"version": 3, "mappings": "", "x": {}{}}}"#,
+			"[".repeat(10),
+			"]".repeat(10)
+		);
+		assert!(SourceMap::parse(&nested).is_ok());
+		let mut mappings = String::new();
+		for _ in 0..3 {
+			encode_vlq(&mut mappings, i64::MAX / 2);
+			mappings.push(',');
+		}
+		let json = format!(r#"{{"version": 3, "mappings": "{}"}}"#, mappings);
+		assert!(SourceMap::parse(&json).unwrap_err().kind() == ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn test_position() {
+		let text = "a\u{1F600}b\nc";
+		let lines = LineIndex::new(text.as_bytes(), crate::LineEndings::Unicode);
+		assert!(position(text, &lines, 5) == (0, 3));
+		assert!(position(text, &lines, 7) == (1, 0));
+		assert!(offset(text, &lines, 0, 3) == Some(5));
+		assert!(offset(text, &lines, 0, 10) == Some(6));
+		assert!(offset(text, &lines, 2, 0) == None);
+	}
+}
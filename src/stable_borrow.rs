@@ -1,10 +1,22 @@
 /// Marker trait to indicate that borrowed references are stable,
 /// even when the owning object is moved.
+///
+/// # Safety
+/// Implementors must guarantee that the borrowed data does not move in memory
+/// when the owning object is moved.
 pub unsafe trait StableBorrow {}
 
-unsafe impl<'a> StableBorrow for &'a str {}
-unsafe impl<'a, T> StableBorrow for &'a [T] {}
+unsafe impl StableBorrow for &str {}
+unsafe impl<T> StableBorrow for &[T] {}
 unsafe impl StableBorrow for String {}
+unsafe impl StableBorrow for &std::ffi::OsStr {}
+unsafe impl StableBorrow for std::ffi::OsString {}
+unsafe impl StableBorrow for &std::path::Path {}
 unsafe impl StableBorrow for std::path::PathBuf {}
+unsafe impl StableBorrow for &std::ffi::CStr {}
+unsafe impl StableBorrow for std::ffi::CString {}
 unsafe impl<T> StableBorrow for Vec<T> {}
 unsafe impl<T> StableBorrow for Box<T> {}
+
+#[cfg(feature = "mmap")]
+unsafe impl StableBorrow for memmap2::Mmap {}
@@ -0,0 +1,242 @@
+// Copyright (c) 2018, Maarten de Vries <maarten@de-vri.es>
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are met:
+//
+// * Redistributions of source code must retain the above copyright notice, this
+//   list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright notice,
+//   this list of conditions and the following disclaimer in the documentation
+//   and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS"
+// AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+// IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+// FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+// DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+// CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+// OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::io::{Error, ErrorKind};
+use std::ops::Range;
+
+use crate::FileData;
+use crate::FileSystem;
+use crate::FileTracker;
+use crate::SliceTracker;
+use crate::SourceLocation;
+use crate::StdFileSystem;
+
+/// The source text of tracked tokens and the byte range of each token in it.
+type TokenSource<'a, T> = (&'a T, Vec<Range<usize>>);
+
+/// Tracker for tokens that maps slices of tokens back to the source text they were lexed from.
+///
+/// The tracker borrows a [`FileTracker`] that tracks the source text.
+/// Each vector of tokens is inserted with the source text it was lexed from and the byte range of each token in it,
+/// so that any slice of the tokens can be mapped to the text spanning those tokens.
+pub struct TokenTracker<'a, Token, Data: FileData, Fs: FileSystem = StdFileSystem> {
+	/// The tracker for the source text.
+	files: &'a FileTracker<Data, Fs>,
+
+	/// The tracked tokens, with the source text and the byte range of each token in it.
+	tokens: SliceTracker<Vec<Token>, TokenSource<'a, Data::Slice>>,
+}
+
+impl<'a, Token, Data: FileData, Fs: FileSystem> TokenTracker<'a, Token, Data, Fs> {
+	/// Create a new token tracker for source text tracked by a file tracker.
+	pub fn new(files: &'a FileTracker<Data, Fs>) -> Self {
+		Self {
+			files,
+			tokens: SliceTracker::new(),
+		}
+	}
+
+	/// Get the file tracker for the source text.
+	pub fn file_tracker(&self) -> &'a FileTracker<Data, Fs> {
+		self.files
+	}
+
+	/// Insert tokens with the source text they were lexed from and the byte range of each token in it.
+	///
+	/// The source text must be tracked by the file tracker.
+	/// The range of a token may be empty, for example for an end-of-file token.
+	///
+	/// Fails if the token type is zero-sized, if the source text is not tracked,
+	/// if the number of tokens and ranges differ, if a range is not a valid subslice of the source text,
+	/// or if the tokens are empty or already (partially) tracked.
+	/// Slices of zero-sized tokens all have the same address, so they can not be told apart.
+	pub fn insert(
+		&self,
+		tokens: Vec<Token>,
+		source: &'a Data::Slice,
+		ranges: impl IntoIterator<Item = Range<usize>>,
+	) -> std::io::Result<&[Token]> {
+		if std::mem::size_of::<Token>() == 0 {
+			return Err(Error::new(
+				ErrorKind::InvalidInput,
+				"zero-sized tokens can not be tracked",
+			));
+		}
+		let ranges: Vec<_> = ranges.into_iter().collect();
+		if !self.files.is_tracked(source) {
+			return Err(Error::new(ErrorKind::InvalidInput, "token source text is not tracked"));
+		}
+		if tokens.len() != ranges.len() {
+			let message = format!("got {} tokens but {} token ranges", tokens.len(), ranges.len());
+			return Err(Error::new(ErrorKind::InvalidInput, message));
+		}
+		if let Some(range) = ranges
+			.iter()
+			.find(|range| Data::get_subslice(source, (*range).clone()).is_none())
+		{
+			let message = format!("token range {:?} is not valid for the source text", range);
+			return Err(Error::new(ErrorKind::InvalidInput, message));
+		}
+		self.tokens
+			.insert(tokens, (source, ranges))
+			.map_err(|()| Error::new(ErrorKind::InvalidInput, "tokens are empty or already tracked"))
+	}
+
+	/// Check if a slice of tokens is tracked.
+	pub fn is_tracked(&self, tokens: &[Token]) -> bool {
+		self.tokens.is_tracked(tokens)
+	}
+
+	/// Get the source text a slice of tokens was lexed from, and the byte range of each token in it.
+	#[allow(clippy::type_complexity)]
+	pub fn token_ranges(&self, tokens: &[Token]) -> Option<(&'a Data::Slice, &[Range<usize>])> {
+		let (whole, (source, ranges)) = self.tokens.get(tokens)?;
+		// Zero-sized tokens are never inserted, so the size is not zero.
+		let start = (tokens.as_ptr() as usize - whole.as_ptr() as usize) / std::mem::size_of::<Token>();
+		Some((source, ranges.get(start..start + tokens.len())?))
+	}
+
+	/// Get the source text spanned by a slice of tokens.
+	///
+	/// The text runs from the start of the first token to the end of the last token.
+	///
+	/// Returns `None` if the tokens are not tracked.
+	pub fn source_text(&self, tokens: &[Token]) -> Option<&'a Data::Slice> {
+		let (source, ranges) = self.token_ranges(tokens)?;
+		let (first, last) = (ranges.first()?, ranges.last()?);
+		Data::get_subslice(source, first.start..last.end.max(first.end))
+	}
+
+	/// Get the source location of the text spanned by a slice of tokens.
+	///
+	/// The location is the start of the text, see [`Self::source_text`] for the text spanned by the tokens
+	/// and [`Self::get_end_location`] for the location of the end of the text.
+	/// See [`FileTracker::get_source_location`] for how the location is reported.
+	/// Unlike for the file tracker, this also works for tokens with an empty range.
+	pub fn get_source_location(&self, tokens: &[Token]) -> Option<SourceLocation<'a, Data::Slice>> {
+		let (source, _) = self.token_ranges(tokens)?;
+		self.files.get_source_location_in(source, self.source_text(tokens)?)
+	}
+
+	/// Get the source location of the end of the text spanned by a slice of tokens.
+	///
+	/// The location is just after the last byte of the text, see [`Self::source_text`].
+	/// Together with [`Self::get_source_location`], this gives the span of the tokens in the source text.
+	pub fn get_end_location(&self, tokens: &[Token]) -> Option<SourceLocation<'a, Data::Slice>> {
+		let (source, _) = self.token_ranges(tokens)?;
+		let text = Data::as_bytes(self.source_text(tokens)?);
+		let end = text.as_ptr() as usize - Data::as_bytes(source).as_ptr() as usize + text.len();
+		self.files
+			.get_source_location_in(source, Data::get_subslice(source, end..end)?)
+	}
+
+	/// Get the source location of the text spanned by a slice of tokens, following expansions back to the original data.
+	///
+	/// See [`FileTracker::resolve_source_location`].
+	pub fn resolve_source_location(&self, tokens: &[Token]) -> Option<SourceLocation<'a, Data::Slice>> {
+		self.files.resolve_location(self.get_source_location(tokens)?)
+	}
+}
+
+impl<Token, Data: FileData, Fs: FileSystem> std::fmt::Debug for TokenTracker<'_, Token, Data, Fs> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("TokenTracker")
+			.field("files", self.files)
+			.finish_non_exhaustive()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::Source;
+	use assert2::assert;
+	use std::path::Path;
+
+	#[derive(Debug, PartialEq)]
+	enum Token {
+		Ident,
+		Number,
+		Plus,
+		Eof,
+	}
+
+	#[test]
+	fn test_token_tracker() {
+		let files = FileTracker::<String>::new();
+		let source = Source::File {
+			path: Path::new("expr.txt").into(),
+			encoding: None,
+			compression: None,
+		};
+		let text = files.insert(String::from("a +\n  12"), source).unwrap();
+
+		let tracker = TokenTracker::new(&files);
+		let tokens = vec![Token::Ident, Token::Plus, Token::Number, Token::Eof];
+		let tokens = tracker.insert(tokens, text, [0..1, 2..3, 6..8, 8..8]).unwrap();
+		assert!(tracker.is_tracked(&tokens[1..]));
+
+		assert!(tracker.source_text(&tokens[1..3]) == Some("+\n  12"));
+		match tracker.get_source_location(&tokens[1..3]) {
+			Some(SourceLocation::File(location)) => assert!(location.to_string() == "expr.txt:1:3"),
+			other => panic!("expected file location, got {:?}", other),
+		}
+		match tracker.resolve_source_location(&tokens[2..3]) {
+			Some(SourceLocation::File(location)) => assert!(location.to_string() == "expr.txt:2:3"),
+			other => panic!("expected file location, got {:?}", other),
+		}
+
+		// Tokens with an empty range are located too.
+		assert!(tracker.source_text(&tokens[3..]) == Some(""));
+		match tracker.get_source_location(&tokens[3..]) {
+			Some(SourceLocation::File(location)) => assert!(location.to_string() == "expr.txt:2:5"),
+			other => panic!("expected file location, got {:?}", other),
+		}
+		assert!(tracker.get_source_location(&tokens[0..0]).is_none());
+
+		// The end location is just after the last token.
+		match tracker.get_end_location(&tokens[1..3]) {
+			Some(SourceLocation::File(location)) => assert!(location.to_string() == "expr.txt:2:5"),
+			other => panic!("expected file location, got {:?}", other),
+		}
+		match tracker.get_end_location(&tokens[..1]) {
+			Some(SourceLocation::File(location)) => assert!(location.to_string() == "expr.txt:1:2"),
+			other => panic!("expected file location, got {:?}", other),
+		}
+
+		// Ranges must be valid for tracked source text.
+		let error = tracker
+			.insert(vec![Token::Eof], text, std::iter::once(7..9))
+			.unwrap_err();
+		assert!(error.kind() == ErrorKind::InvalidInput);
+		assert!(tracker
+			.insert(vec![Token::Eof], "untracked", std::iter::once(0..0))
+			.is_err());
+		assert!(tracker.insert(vec![Token::Eof], text, []).is_err());
+
+		// Zero-sized tokens can not be told apart, so they are rejected.
+		let tracker = TokenTracker::new(&files);
+		let error = tracker.insert(vec![(), ()], text, [0..1, 2..3]).unwrap_err();
+		assert!(error.kind() == ErrorKind::InvalidInput);
+	}
+}